@@ -12,12 +12,11 @@ pub struct ExampleCli {
 }
 
 #[derive(Default, RuntimeDef)]
+#[runtime(mode = "command")]
 #[cli(ExampleCli)]
 pub struct ExampleRuntime;
 
 impl Runtime for ExampleRuntime {
-    const MODE: RuntimeMode = RuntimeMode::Command;
-
     fn deploy<'a>(&mut self, ctx: &mut Context<Self>) -> OutputResponse<'a> {
         if ctx.cli.workdir().is_none() {
             return Error::response("Workdir argument not provided");
@@ -83,6 +82,8 @@ impl Runtime for ExampleRuntime {
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    ya_runtime_sdk::run::<ExampleRuntime>().await
+async fn main() {
+    if let Err(error) = ya_runtime_sdk::run::<ExampleRuntime>().await {
+        std::process::exit(ya_runtime_sdk::exit_code(&error));
+    }
 }
@@ -64,20 +64,22 @@ impl Runtime for ExampleRuntime {
         }
 
         // Echo the executed command and its arguments
-        let started = tokio::process::Command::new("/bin/echo")
-            .arg(command.bin)
-            .args(command.args)
+        let mut echo = tokio::process::Command::new("/bin/echo");
+        echo.arg(&command.bin)
+            .args(&command.args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .stdin(Stdio::null())
-            .spawn();
+            .stdin(Stdio::null());
 
         // Wraps command's lifecycle. The handler is executed in background.
-        // Result of `started` is handled prior to emitting command lifecycle events.
-        futures::future::ready(started).as_command(ctx, |child, mut run_ctx| async move {
-            let output = child.wait_with_output().await?;
-            run_ctx.stdout(output.stdout).await;
-            run_ctx.stderr(output.stderr).await;
+        // Processes spawned with `run_ctx.spawn` are killed when the command times out.
+        ctx.command_for(&command, |run_ctx| async move {
+            let mut child = run_ctx.spawn(echo)?;
+            // Emit output as soon as it becomes available
+            let stdout = run_ctx.pipe_stdout(child.stdout.take().unwrap());
+            let stderr = run_ctx.pipe_stderr(child.stderr.take().unwrap());
+            futures::future::try_join(stdout, stderr).await?;
+            child.wait().await?;
             Ok(())
         })
     }
@@ -1,11 +1,12 @@
 extern crate proc_macro;
-use std::collections::HashSet;
+use proc_macro2::Span;
+use syn::spanned::Spanned;
 
-#[proc_macro_derive(RuntimeDef, attributes(cli, conf))]
+#[proc_macro_derive(RuntimeDef, attributes(cli, conf, runtime))]
 pub fn derive_runtime_def(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let mut parsed = syn::parse_macro_input!(stream as syn::Item);
     match &mut parsed {
-        syn::Item::Struct(item) => impl_mod_struct(item),
+        syn::Item::Struct(item) => impl_mod_struct(item).unwrap_or_else(|e| e.to_compile_error()),
         _ => {
             let error = syn::Error::new(proc_macro2::Span::call_site(), "not a struct type");
             error.to_compile_error()
@@ -14,128 +15,540 @@ pub fn derive_runtime_def(stream: proc_macro::TokenStream) -> proc_macro::TokenS
     .into()
 }
 
-fn impl_mod_struct(item: &syn::ItemStruct) -> proc_macro2::TokenStream {
-    let attrs = parse_attributes(&item.attrs);
+#[proc_macro_derive(RuntimeError, attributes(runtime_error))]
+pub fn derive_runtime_error(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let parsed = syn::parse_macro_input!(stream as syn::Item);
+    match &parsed {
+        syn::Item::Enum(item) => impl_error_enum(item).unwrap_or_else(|e| e.to_compile_error()),
+        _ => {
+            let error = syn::Error::new(proc_macro2::Span::call_site(), "not an enum type");
+            error.to_compile_error()
+        }
+    }
+    .into()
+}
+
+#[proc_macro_derive(RuntimeCommands, attributes(command))]
+pub fn derive_runtime_commands(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let parsed = syn::parse_macro_input!(stream as syn::Item);
+    match &parsed {
+        syn::Item::Enum(item) => impl_commands_enum(item).unwrap_or_else(|e| e.to_compile_error()),
+        _ => {
+            let error = syn::Error::new(proc_macro2::Span::call_site(), "not an enum type");
+            error.to_compile_error()
+        }
+    }
+    .into()
+}
+
+fn impl_mod_struct(item: &syn::ItemStruct) -> syn::Result<proc_macro2::TokenStream> {
+    let attrs = parse_attributes(&item.attrs)?;
+    for attr in attrs.iter() {
+        if let DefParam::Cli(path) = attr {
+            check_generic_params(path, &item.generics)?;
+        }
+    }
     let runtime = item.ident.clone();
     let generics = item.generics.clone();
-    impl_mod(attrs, runtime, generics)
+    Ok(impl_mod(attrs, runtime, generics))
 }
 
 fn impl_mod(
-    attrs: HashSet<DefParam>,
+    attrs: Vec<DefParam>,
     name: syn::Ident,
     generics: syn::Generics,
 ) -> proc_macro2::TokenStream {
+    let module = quote::format_ident!("ya_runtime_sdk_impl");
     let mut impl_cli = quote::quote!();
     let mut impl_conf = quote::quote!(
         #[derive(Default, ::serde::Serialize, ::serde::Deserialize)]
         pub struct Conf {}
     );
+    let mut impl_conf_type = quote::quote!(#module::Conf);
+    let mut impl_name = quote::quote!(env!("CARGO_PKG_NAME"));
+    let mut impl_version = quote::quote!(env!("CARGO_PKG_VERSION"));
+    let mut impl_mode = quote::quote!();
 
     for attr in attrs {
         match attr {
-            DefParam::Cli(ident) => {
+            DefParam::Name(lit) => impl_name = quote::quote!(#lit),
+            DefParam::Version(lit) => impl_version = quote::quote!(#lit),
+            DefParam::Mode(mode) => {
+                impl_mode = quote::quote!(
+                    const DEFAULT_MODE: ::ya_runtime_sdk::RuntimeMode = ::ya_runtime_sdk::RuntimeMode::#mode;
+                )
+            }
+            DefParam::Cli(path) => {
+                let path = qualify_path(&path);
                 impl_cli = quote::quote!(
                     #[structopt(flatten)]
-                    pub runtime: super::#ident,
+                    pub runtime: #path,
                 );
             }
-            DefParam::Conf(ident) => {
-                impl_conf = quote::quote!(
-                    pub type Conf = super::#ident;
-                )
+            DefParam::Conf(ty) => {
+                impl_conf = quote::quote!();
+                impl_conf_type = quote::quote!(#ty);
             }
         }
     }
 
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
+    // the module is scoped to an anonymous constant, thus does not collide with other derives
     quote::quote!(
+        const _: () = {
+            #[doc(hidden)]
+            pub mod #module {
+                #[derive(structopt::StructOpt)]
+                #[structopt(setting = structopt::clap::AppSettings::ColoredHelp)]
+                #[structopt(setting = structopt::clap::AppSettings::DeriveDisplayOrder)]
+                #[structopt(setting = structopt::clap::AppSettings::VersionlessSubcommands)]
+                pub struct Cli {
+                    /// Working directory
+                    #[structopt(short, long)]
+                    #[structopt(required_ifs(&[
+                        ("command", "deploy"),
+                        ("command", "start"),
+                        ("command", "run"),
+                    ]))]
+                    pub workdir: Option<std::path::PathBuf>,
 
-        #[doc(hidden)]
-        pub mod ya_runtime_sdk_impl {
-            #[derive(structopt::StructOpt)]
-            #[structopt(setting = structopt::clap::AppSettings::ColoredHelp)]
-            #[structopt(setting = structopt::clap::AppSettings::DeriveDisplayOrder)]
-            #[structopt(setting = structopt::clap::AppSettings::VersionlessSubcommands)]
-            pub struct Cli {
-                /// Working directory
-                #[structopt(short, long)]
-                #[structopt(required_ifs(&[
-                    ("command", "deploy"),
-                    ("command", "start"),
-                    ("command", "run"),
-                ]))]
-                pub workdir: Option<std::path::PathBuf>,
-
-                #impl_cli
-
-                /// Command to execute
-                #[structopt(subcommand)]
-                pub command: ::ya_runtime_sdk::cli::Command,
-            }
+                    /// Pretty-print JSON output
+                    #[structopt(long)]
+                    pub pretty: bool,
+
+                    #impl_cli
 
-            impl ::ya_runtime_sdk::cli::CommandCli for Cli {
-                fn workdir(&self) -> Option<std::path::PathBuf> {
-                    self.workdir.clone()
+                    /// Command to execute
+                    #[structopt(subcommand)]
+                    pub command: ::ya_runtime_sdk::cli::Command,
                 }
 
-                fn command(&self) -> &::ya_runtime_sdk::cli::Command {
-                    &self.command
+                impl ::ya_runtime_sdk::cli::CommandCli for Cli {
+                    fn workdir(&self) -> Option<std::path::PathBuf> {
+                        self.workdir.clone()
+                    }
+
+                    fn pretty(&self) -> bool {
+                        self.pretty
+                    }
+
+                    fn command(&self) -> &::ya_runtime_sdk::cli::Command {
+                        &self.command
+                    }
                 }
-            }
 
-            #impl_conf
-        }
+                #impl_conf
+            }
 
-        impl #impl_generics ::ya_runtime_sdk::RuntimeDef for #name #ty_generics #where_clause {
-            const NAME: &'static str = env!("CARGO_PKG_NAME");
-            const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+            impl #impl_generics ::ya_runtime_sdk::RuntimeDef for #name #ty_generics #where_clause {
+                const NAME: &'static str = #impl_name;
+                const VERSION: &'static str = #impl_version;
+                #impl_mode
 
-            type Cli = ya_runtime_sdk_impl::Cli;
-            type Conf = ya_runtime_sdk_impl::Conf;
-        }
+                type Cli = #module::Cli;
+                type Conf = #impl_conf_type;
+            }
+        };
     )
 }
 
-fn parse_attributes(attrs: &[syn::Attribute]) -> HashSet<DefParam> {
-    attrs
+/// The generated `Cli` is not generic, thus cannot contain a `cli` type which depends
+/// on generic parameters of the runtime
+fn check_generic_params(path: &syn::Path, generics: &syn::Generics) -> syn::Result<()> {
+    let params = generics
+        .params
         .iter()
-        .map(|attr| (attr, attr.path.segments[0].ident.to_string()))
-        .filter(|(_, variant)| DefParam::VARIANTS.iter().any(|v| *v == variant.as_str()))
-        .map(|(attr, variant)| {
-            let ident = syn::parse2::<DefIdent>(attr.tokens.clone())
-                .unwrap_or_else(|_| panic!("invalid value {}", variant));
-            DefParam::new(&variant, ident.0)
+        .map(|param| match param {
+            syn::GenericParam::Type(param) => param.ident.clone(),
+            syn::GenericParam::Lifetime(param) => param.lifetime.ident.clone(),
+            syn::GenericParam::Const(param) => param.ident.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    fn find(stream: proc_macro2::TokenStream, params: &[syn::Ident]) -> Option<syn::Ident> {
+        stream.into_iter().find_map(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) if params.contains(&ident) => Some(ident),
+            proc_macro2::TokenTree::Group(group) => find(group.stream(), params),
+            _ => None,
+        })
+    }
+
+    match find(quote::quote!(#path), &params) {
+        Some(ident) => Err(syn::Error::new(
+            ident.span(),
+            "the `cli` type cannot depend on generic parameters of the runtime",
+        )),
+        None => Ok(()),
+    }
+}
+
+fn parse_attributes(attrs: &[syn::Attribute]) -> syn::Result<Vec<DefParam>> {
+    let mut params: Vec<DefParam> = Vec::new();
+
+    for attr in attrs {
+        let parsed = if attr.path.is_ident("cli") {
+            let path = syn::parse2::<DefValue<syn::Path>>(attr.tokens.clone()).map_err(|_| {
+                syn::Error::new_spanned(attr, "expected a type path, e.g. `#[cli(RuntimeCli)]`")
+            })?;
+            vec![(DefParam::Cli(path.0), attr.path.span())]
+        } else if attr.path.is_ident("conf") {
+            let ty = syn::parse2::<DefValue<syn::Type>>(attr.tokens.clone()).map_err(|_| {
+                syn::Error::new_spanned(attr, "expected a type, e.g. `#[conf(RuntimeConf)]`")
+            })?;
+            vec![(DefParam::Conf(Box::new(ty.0)), attr.path.span())]
+        } else if attr.path.is_ident("runtime") {
+            parse_runtime_attribute(attr)?
+        } else {
+            continue;
+        };
+
+        for (param, span) in parsed {
+            if params.iter().any(|p| p.variant() == param.variant()) {
+                let message = format!("duplicate `{}` attribute", param.variant());
+                return Err(syn::Error::new(span, message));
+            }
+            params.push(param);
+        }
+    }
+
+    Ok(params)
+}
+
+/// Parses `#[runtime(name = "...", version = "...", mode = "...")]`
+fn parse_runtime_attribute(attr: &syn::Attribute) -> syn::Result<Vec<(DefParam, Span)>> {
+    let list = match attr.parse_meta()? {
+        syn::Meta::List(list) => list,
+        meta => return Err(syn::Error::new_spanned(meta, "expected runtime(..)")),
+    };
+
+    list.nested
+        .into_iter()
+        .map(|nested| {
+            let nv = match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) => nv,
+                other => return Err(syn::Error::new_spanned(other, "expected `key = \"value\"`")),
+            };
+            let lit = match &nv.lit {
+                syn::Lit::Str(lit) => lit.clone(),
+                other => return Err(syn::Error::new_spanned(other, "expected a string literal")),
+            };
+            let key = nv.path.get_ident().map(ToString::to_string);
+
+            let param = match key.as_deref() {
+                Some("name") => {
+                    validate_name(&lit)?;
+                    DefParam::Name(lit)
+                }
+                Some("version") => {
+                    validate_version(&lit)?;
+                    DefParam::Version(lit)
+                }
+                Some("mode") => match lit.value().as_str() {
+                    "server" => DefParam::Mode(quote::format_ident!("Server")),
+                    "command" => DefParam::Mode(quote::format_ident!("Command")),
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            lit,
+                            "expected \"server\" or \"command\"",
+                        ))
+                    }
+                },
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        nv.path,
+                        "expected one of: name, version, mode",
+                    ))
+                }
+            };
+            Ok((param, nv.path.span()))
         })
         .collect()
 }
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+fn validate_name(lit: &syn::LitStr) -> syn::Result<()> {
+    let value = lit.value();
+    let valid = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    if value.is_empty() || !valid || value.starts_with('.') {
+        return Err(syn::Error::new_spanned(
+            lit,
+            "runtime name must be a non-empty string of ASCII alphanumerics, '-', '_' and '.'",
+        ));
+    }
+    Ok(())
+}
+
+fn validate_version(lit: &syn::LitStr) -> syn::Result<()> {
+    let value = lit.value();
+    let core = value.split(['-', '+']).next().unwrap_or_default();
+    let numbers = core.split('.').collect::<Vec<_>>();
+    let valid = numbers.len() == 3
+        && numbers
+            .iter()
+            .all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+
+    if !valid {
+        return Err(syn::Error::new_spanned(
+            lit,
+            "runtime version must follow the `MAJOR.MINOR.PATCH` format",
+        ));
+    }
+    Ok(())
+}
+
 enum DefParam {
-    Cli(syn::Ident),
-    Conf(syn::Ident),
+    Cli(syn::Path),
+    Conf(Box<syn::Type>),
+    Name(syn::LitStr),
+    Version(syn::LitStr),
+    Mode(syn::Ident),
 }
 
 impl DefParam {
-    const VARIANTS: [&'static str; 2] = ["cli", "conf"];
-
-    fn new(variant: &str, ident: syn::Ident) -> Self {
-        match variant {
-            "cli" => DefParam::Cli(ident),
-            "conf" => DefParam::Conf(ident),
-            _ => panic!("invalid attribute: {}", variant),
+    fn variant(&self) -> &'static str {
+        match self {
+            DefParam::Cli(_) => "cli",
+            DefParam::Conf(_) => "conf",
+            DefParam::Name(_) => "name",
+            DefParam::Version(_) => "version",
+            DefParam::Mode(_) => "mode",
         }
     }
 }
 
-struct DefIdent(syn::Ident);
+struct DefValue<T>(T);
 
-impl syn::parse::Parse for DefIdent {
+impl<T: syn::parse::Parse> syn::parse::Parse for DefValue<T> {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let content;
         syn::parenthesized!(content in input);
         let item = content.parse()?;
-        Ok(DefIdent(item))
+        Ok(DefValue(item))
+    }
+}
+
+/// Resolves a path relative to the parent of the generated module
+fn qualify_path(path: &syn::Path) -> proc_macro2::TokenStream {
+    let first = path.segments.first().map(|s| s.ident.to_string());
+    match first.as_deref() {
+        _ if path.leading_colon.is_some() => quote::quote!(#path),
+        Some("crate") => quote::quote!(#path),
+        Some("self") => {
+            let rest = path.segments.iter().skip(1);
+            quote::quote!(super #(::#rest)*)
+        }
+        _ => quote::quote!(super::#path),
+    }
+}
+
+fn impl_error_enum(item: &syn::ItemEnum) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &item.ident;
+    let default = parse_error_code(&item.attrs)?
+        .unwrap_or_else(|| quote::quote!(::ya_runtime_sdk::ErrorKind::Internal.code()));
+
+    let arms = item
+        .variants
+        .iter()
+        .map(|variant| {
+            let ident = &variant.ident;
+            let code = parse_error_code(&variant.attrs)?.unwrap_or_else(|| default.clone());
+            Ok(quote::quote!(#name::#ident { .. } => #code,))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+
+    Ok(quote::quote!(
+        impl #impl_generics From<#name #ty_generics> for ::ya_runtime_sdk::Error #where_clause {
+            fn from(e: #name #ty_generics) -> Self {
+                let code = match &e {
+                    #(#arms)*
+                };
+                ::ya_runtime_sdk::Error::from_code(code, e)
+            }
+        }
+
+        impl #impl_generics From<#name #ty_generics> for ::ya_runtime_sdk::ErrorResponse #where_clause {
+            fn from(e: #name #ty_generics) -> Self {
+                ::ya_runtime_sdk::Error::from(e).into()
+            }
+        }
+    ))
+}
+
+/// Variants of `ya_runtime_sdk::ErrorKind`, accepted in snake case by `#[runtime_error(kind = "...")]`
+const ERROR_KINDS: &[&str] = &[
+    "InvalidArgument",
+    "NotSupported",
+    "NotFound",
+    "Timeout",
+    "Busy",
+    "Internal",
+];
+
+/// Parses `#[runtime_error(kind = "...")]` and `#[runtime_error(code = ...)]` attributes
+fn parse_error_code(attrs: &[syn::Attribute]) -> syn::Result<Option<proc_macro2::TokenStream>> {
+    let mut code = None;
+
+    for attr in attrs.iter().filter(|a| a.path.is_ident("runtime_error")) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected runtime_error(..)")),
+        };
+
+        for nested in list.nested {
+            let nv = match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) => nv,
+                other => return Err(syn::Error::new_spanned(other, "expected `key = value`")),
+            };
+            if code.is_some() {
+                return Err(syn::Error::new_spanned(nv, "error code already specified"));
+            }
+
+            code = Some(match (nv.path.get_ident(), &nv.lit) {
+                (Some(key), syn::Lit::Str(lit)) if key == "kind" => {
+                    let value = lit.value();
+                    let kind = ERROR_KINDS
+                        .iter()
+                        .find(|kind| separated_case(kind, '_') == value)
+                        .ok_or_else(|| {
+                            let names = ERROR_KINDS
+                                .iter()
+                                .map(|kind| separated_case(kind, '_'))
+                                .collect::<Vec<_>>();
+                            syn::Error::new_spanned(
+                                lit,
+                                format!("expected one of: {}", names.join(", ")),
+                            )
+                        })?;
+                    let kind = quote::format_ident!("{}", kind);
+                    quote::quote!(::ya_runtime_sdk::ErrorKind::#kind.code())
+                }
+                (Some(key), syn::Lit::Int(lit)) if key == "code" => {
+                    let value = lit.base10_parse::<i32>()?;
+                    quote::quote!(#value)
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        nv,
+                        "expected `kind = \"...\"` or `code = <i32>`",
+                    ))
+                }
+            });
+        }
+    }
+
+    Ok(code)
+}
+
+fn impl_commands_enum(item: &syn::ItemEnum) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &item.ident;
+    let mut names = Vec::new();
+    let mut arms = Vec::new();
+
+    for variant in item.variants.iter() {
+        let ident = &variant.ident;
+        let command = parse_command_name(&variant.attrs)?
+            .unwrap_or_else(|| separated_case(&ident.to_string(), '-'));
+
+        if names.contains(&command) {
+            let message = format!("duplicate command name: {}", command);
+            return Err(syn::Error::new_spanned(variant, message));
+        }
+
+        arms.push(match &variant.fields {
+            syn::Fields::Unit => quote::quote!(
+                #command => {
+                    ::ya_runtime_sdk::commands::parse_empty(name, args)?;
+                    Ok(#name::#ident)
+                }
+            ),
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                quote::quote!(
+                    #command => Ok(#name::#ident(
+                        ::ya_runtime_sdk::commands::parse_args::<#ty>(name, args)?
+                    )),
+                )
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "expected a unit variant or a tuple variant with a single `StructOpt` field",
+                ))
+            }
+        });
+        names.push(command);
+    }
+
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+
+    Ok(quote::quote!(
+        impl #impl_generics ::ya_runtime_sdk::RuntimeCommands for #name #ty_generics #where_clause {
+            fn names() -> &'static [&'static str] {
+                &[#(#names),*]
+            }
+
+            fn parse(
+                name: &str,
+                args: &[String],
+            ) -> Result<Self, ::ya_runtime_sdk::Error> {
+                match name {
+                    #(#arms)*
+                    _ => Err(::ya_runtime_sdk::commands::unknown_command(name, Self::names())),
+                }
+            }
+        }
+    ))
+}
+
+/// Parses `#[command(name = "...")]` attributes
+fn parse_command_name(attrs: &[syn::Attribute]) -> syn::Result<Option<String>> {
+    let mut name = None;
+
+    for attr in attrs.iter().filter(|a| a.path.is_ident("command")) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected command(..)")),
+        };
+
+        for nested in list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit),
+                    ..
+                })) if path.is_ident("name") => {
+                    if lit.value().is_empty() {
+                        return Err(syn::Error::new_spanned(lit, "command name cannot be empty"));
+                    }
+                    if name.replace(lit.value()).is_some() {
+                        return Err(syn::Error::new_spanned(lit, "duplicate command name"));
+                    }
+                }
+                other => return Err(syn::Error::new_spanned(other, "expected `name = \"...\"`")),
+            }
+        }
+    }
+
+    Ok(name)
+}
+
+/// Converts a CamelCase identifier to lowercase words joined with `separator`
+fn separated_case(ident: &str, separator: char) -> String {
+    let mut result = String::new();
+    for (i, c) in ident.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 && !result.ends_with(separator) {
+                result.push(separator);
+            }
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
     }
+    result
 }
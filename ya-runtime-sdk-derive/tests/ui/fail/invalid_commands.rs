@@ -0,0 +1,20 @@
+use ya_runtime_sdk::{RuntimeCommands, RuntimeError};
+
+#[derive(RuntimeCommands)]
+pub enum Commands {
+    Run { path: String },
+}
+
+#[derive(Debug, RuntimeError)]
+pub enum Error {
+    #[runtime_error(kind = "unknown")]
+    Unknown,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+fn main() {}
@@ -0,0 +1,29 @@
+use ya_runtime_sdk::RuntimeDef;
+
+#[derive(Default, RuntimeDef)]
+#[runtime(name = "invalid name")]
+pub struct InvalidName;
+
+#[derive(Default, RuntimeDef)]
+#[runtime(version = "1.0")]
+pub struct InvalidVersion;
+
+#[derive(Default, RuntimeDef)]
+#[runtime(mode = "daemon")]
+pub struct InvalidMode;
+
+#[derive(Default, RuntimeDef)]
+#[runtime(name = "first")]
+#[runtime(name = "second")]
+pub struct DuplicateName;
+
+#[derive(RuntimeDef)]
+pub enum NotAStruct {}
+
+#[derive(Default, RuntimeDef)]
+#[cli(GenericCli<T>)]
+pub struct GenericCliRuntime<T> {
+    _marker: std::marker::PhantomData<T>,
+}
+
+fn main() {}
@@ -0,0 +1,31 @@
+use structopt::StructOpt;
+use ya_runtime_sdk::RuntimeDef;
+
+mod args {
+    #[derive(structopt::StructOpt)]
+    pub struct Cli {
+        #[structopt(long)]
+        pub verbose: bool,
+    }
+}
+
+#[derive(Default, RuntimeDef)]
+#[cli(args::Cli)]
+pub struct Runtime;
+
+#[derive(Default, RuntimeDef)]
+#[cli(crate::args::Cli)]
+pub struct CrateRuntime;
+
+#[derive(Default, RuntimeDef)]
+#[cli(self::args::Cli)]
+pub struct SelfRuntime;
+
+fn main() {
+    let cli = <Runtime as RuntimeDef>::Cli::from_iter(&["bin", "--verbose", "test"]);
+    assert!(cli.runtime.verbose);
+    let cli = <CrateRuntime as RuntimeDef>::Cli::from_iter(&["bin", "test"]);
+    assert!(!cli.runtime.verbose);
+    let cli = <SelfRuntime as RuntimeDef>::Cli::from_iter(&["bin", "test"]);
+    assert!(!cli.runtime.verbose);
+}
@@ -0,0 +1,43 @@
+use ya_runtime_sdk::{ErrorKind, RuntimeError};
+
+#[derive(Debug, RuntimeError)]
+pub enum Error {
+    #[runtime_error(kind = "invalid_argument")]
+    InvalidArgument,
+    #[runtime_error(kind = "not_supported")]
+    NotSupported,
+    #[runtime_error(kind = "not_found")]
+    NotFound,
+    #[runtime_error(kind = "timeout")]
+    Timeout,
+    #[runtime_error(kind = "busy")]
+    Busy,
+    #[runtime_error(kind = "internal")]
+    Internal,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// fails to compile when a kind is missing above
+fn variant(kind: ErrorKind) -> Error {
+    match kind {
+        ErrorKind::InvalidArgument => Error::InvalidArgument,
+        ErrorKind::NotSupported => Error::NotSupported,
+        ErrorKind::NotFound => Error::NotFound,
+        ErrorKind::Timeout => Error::Timeout,
+        ErrorKind::Busy => Error::Busy,
+        ErrorKind::Internal => Error::Internal,
+    }
+}
+
+fn main() {
+    for code in 0..256 {
+        if let Some(kind) = ErrorKind::from_code(code) {
+            assert_eq!(ya_runtime_sdk::Error::from(variant(kind)).code(), code);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use ya_runtime_sdk::RuntimeDef;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Conf<T> {
+    pub value: T,
+}
+
+#[derive(Default, RuntimeDef)]
+#[conf(Conf<T>)]
+pub struct Runtime<T: Default + Serialize + DeserializeOwned> {
+    _marker: PhantomData<T>,
+}
+
+fn default_conf<R: RuntimeDef>() -> R::Conf {
+    Default::default()
+}
+
+fn main() {
+    let conf: Conf<u32> = default_conf::<Runtime<u32>>();
+    assert_eq!(conf.value, 0);
+    let conf: Conf<String> = default_conf::<Runtime<String>>();
+    assert!(conf.value.is_empty());
+}
@@ -0,0 +1,21 @@
+use ya_runtime_sdk::RuntimeDef;
+
+#[derive(Default, RuntimeDef)]
+#[runtime(name = "first")]
+pub struct FirstRuntime;
+
+#[derive(Default, RuntimeDef)]
+#[runtime(name = "second")]
+pub struct SecondRuntime;
+
+// named as `FirstRuntime` in snake case
+#[allow(non_camel_case_types)]
+#[derive(Default, RuntimeDef)]
+#[runtime(name = "third")]
+pub struct First_Runtime;
+
+fn main() {
+    assert_eq!(FirstRuntime::NAME, "first");
+    assert_eq!(SecondRuntime::NAME, "second");
+    assert_eq!(First_Runtime::NAME, "third");
+}
@@ -0,0 +1,96 @@
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use structopt::StructOpt;
+use tokio::process::Command;
+
+use ya_runtime_api::server::{
+    spawn, ProcessStatus, RuntimeControl, RuntimeHandler, RuntimeService, RuntimeStatus,
+};
+use ya_runtime_sdk::debugger::{format_process_status, format_runtime_status, repl};
+
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interactive debugging client for `Server` mode runtimes
+#[derive(StructOpt)]
+struct Args {
+    /// Runtime binary
+    runtime: PathBuf,
+    /// Runtime working directory. Defaults to a new temporary directory
+    #[structopt(long)]
+    workdir: Option<PathBuf>,
+    /// Skip runtime deployment
+    #[structopt(long)]
+    no_deploy: bool,
+    /// Additional runtime arguments
+    #[structopt(last = true)]
+    args: Vec<String>,
+}
+
+impl Args {
+    fn command(&self, workdir: &Path, command: &str) -> Command {
+        let mut cmd = Command::new(&self.runtime);
+        cmd.args(&self.args)
+            .arg("--workdir")
+            .arg(workdir)
+            .arg(command)
+            .stderr(Stdio::inherit());
+        cmd
+    }
+}
+
+struct EventHandler;
+
+impl RuntimeHandler for EventHandler {
+    fn on_process_status<'a>(&self, status: ProcessStatus) -> BoxFuture<'a, ()> {
+        println!("{}", format_process_status(&status));
+        futures::future::ready(()).boxed()
+    }
+
+    fn on_runtime_status<'a>(&self, status: RuntimeStatus) -> BoxFuture<'a, ()> {
+        println!("{}", format_runtime_status(&status));
+        futures::future::ready(()).boxed()
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::from_args();
+    let workdir = match args.workdir {
+        Some(ref workdir) => workdir.clone(),
+        None => std::env::temp_dir().join(format!("ya-runtime-debugger-{}", std::process::id())),
+    };
+    std::fs::create_dir_all(&workdir)?;
+    println!("working directory: {}", workdir.display());
+
+    if !args.no_deploy {
+        let output = args.command(&workdir, "deploy").output().await?;
+        if !output.status.success() {
+            anyhow::bail!("Deployment failed: {}", output.status);
+        }
+        let deployment: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        println!("deployment: {}", serde_json::to_string_pretty(&deployment)?);
+    }
+
+    let service = spawn(args.command(&workdir, "start"), EventHandler).await?;
+    let version = service
+        .hello(env!("CARGO_PKG_VERSION"))
+        .await
+        .map_err(|e| anyhow::anyhow!("Runtime handshake failed: {}", e.message))?;
+    println!("runtime version: {}", version);
+
+    let input = tokio::io::BufReader::new(tokio::io::stdin());
+    let work_dir = workdir.display().to_string();
+    repl(&service, input, &mut std::io::stdout(), &work_dir).await?;
+
+    match tokio::time::timeout(STOP_TIMEOUT, service.stopped()).await {
+        Ok(code) => println!("runtime exited with code {}", code),
+        Err(_) => {
+            println!("runtime did not exit in {:?}, killing", STOP_TIMEOUT);
+            service.stop();
+        }
+    }
+    Ok(())
+}
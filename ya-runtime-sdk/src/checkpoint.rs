@@ -0,0 +1,55 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::runtime::{ProcessId, Runtime};
+use crate::serialize::json;
+
+/// Runtime state persistence for runtimes running in `RuntimeMode::Server`.
+///
+/// When enabled with `Context::enable_checkpoints`, a snapshot of the runtime
+/// is written to the working directory periodically and after the runtime is stopped.
+/// The latest snapshot is restored prior to starting the runtime.
+pub trait Checkpoint: Runtime + Sized {
+    /// Interval between periodic checkpoints
+    const INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Serializable runtime state
+    type Snapshot: Serialize + DeserializeOwned;
+
+    /// Capture the runtime state
+    fn snapshot(&self) -> Self::Snapshot;
+
+    /// Rehydrate the runtime from a previously captured state.
+    /// On error, the runtime starts fresh and should be left unchanged
+    fn restore(&mut self, snapshot: Self::Snapshot) -> Result<(), Error>;
+}
+
+/// Checkpoint contents, stored in `StateStore`
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Snapshot {
+    pub pid_seq: ProcessId,
+    pub runtime: json::Value,
+}
+
+impl Snapshot {
+    pub const KEY: &'static str = "ya-runtime-sdk.checkpoint";
+}
+
+/// Type-erased checkpoint handlers of a `Checkpoint` runtime
+pub(crate) struct Checkpoints<R: Runtime + ?Sized> {
+    pub interval: Duration,
+    pub save: fn(&Context<R>, &R) -> Result<(), Error>,
+    pub restore: fn(&mut Context<R>, &mut R) -> Result<bool, Error>,
+}
+
+impl<R: Runtime + ?Sized> Clone for Checkpoints<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R: Runtime + ?Sized> Copy for Checkpoints<R> {}
@@ -13,6 +13,10 @@ pub fn parse_cli<C: CommandCli>(
 
 pub trait CommandCli: StructOpt + Send {
     fn workdir(&self) -> Option<PathBuf>;
+    /// Whether to pretty-print JSON output
+    fn pretty(&self) -> bool {
+        false
+    }
     fn command(&self) -> &Command;
 }
 
@@ -29,6 +33,20 @@ pub enum Command {
     OfferTemplate { args: Vec<String> },
     /// Perform a self-test
     Test { args: Vec<String> },
+    /// Output a plugin descriptor JSON for the Provider Agent
+    PluginDescriptor {
+        /// Descriptor description
+        #[structopt(long)]
+        description: Option<String>,
+        /// ExeUnit Supervisor path
+        #[structopt(long)]
+        supervisor_path: Option<PathBuf>,
+        /// Include a preset entry with the runtime usage counters
+        #[structopt(long)]
+        preset: bool,
+        /// Extra arguments passed to the runtime by the ExeUnit Supervisor
+        args: Vec<String>,
+    },
 }
 
 impl Command {
@@ -38,7 +56,8 @@ impl Command {
             | Self::Start { args }
             | Self::Run { args }
             | Self::OfferTemplate { args }
-            | Self::Test { args } => args,
+            | Self::Test { args }
+            | Self::PluginDescriptor { args, .. } => args,
         }
     }
 }
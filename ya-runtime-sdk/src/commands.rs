@@ -0,0 +1,50 @@
+use structopt::StructOpt;
+
+use crate::error::Error;
+use crate::RunProcess;
+
+/// Set of commands supported by the runtime, parsed from `RunProcess` requests.
+/// Auto-generated with `#[derive(RuntimeCommands)]`
+pub trait RuntimeCommands: Sized + 'static {
+    /// Names of supported commands
+    fn names() -> &'static [&'static str];
+
+    /// Parse a command name and its arguments
+    fn parse(name: &str, args: &[String]) -> Result<Self, Error>;
+
+    /// Parse a `RunProcess` request
+    fn from_run_process(command: &RunProcess) -> Result<Self, Error> {
+        Self::parse(&command.bin, &command.args)
+    }
+}
+
+/// Parse command arguments into a `StructOpt` struct
+pub fn parse_args<T: StructOpt>(name: &str, args: &[String]) -> Result<T, Error> {
+    let args = std::iter::once(name.to_string()).chain(args.iter().cloned());
+    let matches = T::clap()
+        .name(name)
+        .get_matches_from_safe(args)
+        .map_err(|e| Error::invalid_argument(e.message))?;
+    Ok(T::from_clap(&matches))
+}
+
+/// Ensure that a command without arguments was called without any
+pub fn parse_empty(name: &str, args: &[String]) -> Result<(), Error> {
+    match args.is_empty() {
+        true => Ok(()),
+        false => Err(Error::invalid_argument(format!(
+            "command '{}' does not accept arguments, got: {}",
+            name,
+            args.join(" ")
+        ))),
+    }
+}
+
+/// Error returned for unknown command names
+pub fn unknown_command(name: &str, names: &[&str]) -> Error {
+    Error::not_supported(format!(
+        "unknown command '{}', expected one of: {}",
+        name,
+        names.join(", ")
+    ))
+}
@@ -1,5 +1,6 @@
+use std::any::Any;
 use std::borrow::Cow;
-use tokio::io::AsyncWriteExt;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub trait IntoVec<T> {
     fn into_vec(self) -> Vec<T>;
@@ -41,10 +42,47 @@ impl<'a> IntoVec<u8> for Cow<'a, str> {
     }
 }
 
-pub(crate) async fn write_output(json: serde_json::Value) -> anyhow::Result<()> {
-    let string = json.to_string();
-    let mut stdout = tokio::io::stdout();
-    stdout.write_all(string.as_bytes()).await?;
-    stdout.flush().await?;
-    Ok(())
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Return the current time in milliseconds since the Unix epoch
+pub(crate) fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Serializes valid UTF-8 bytes as a string, and an array of numbers otherwise
+pub(crate) mod bytes {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Bytes {
+        Text(String),
+        Binary(Vec<u8>),
+    }
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        match String::from_utf8(bytes.to_vec()) {
+            Ok(s) => Bytes::Text(s),
+            Err(e) => Bytes::Binary(e.into_bytes()),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        Ok(match Bytes::deserialize(deserializer)? {
+            Bytes::Text(s) => s.into_bytes(),
+            Bytes::Binary(b) => b,
+        })
+    }
 }
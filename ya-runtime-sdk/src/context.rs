@@ -5,21 +5,38 @@ use serde::Serialize;
 use std::cell::RefCell;
 use std::future::Future;
 use std::io::Write;
+use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering::Relaxed;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 use ya_runtime_api::server::{RuntimeCounter, RuntimeHandler, RuntimeState};
 
-use crate::common::{write_output, IntoVec};
+use crate::checkpoint::{Checkpoint, Checkpoints, Snapshot};
+use crate::cli::CommandCli;
+use crate::commands::RuntimeCommands;
+use crate::common::{panic_message, IntoVec};
 use crate::env::{DefaultEnv, Env};
 use crate::error::Error;
 use crate::event::EventEmitter;
-use crate::runtime::{ProcessId, ProcessIdResponse};
+use crate::limits::{Children, LimitedChild, Limits, ReturnCode};
+use crate::output::{Capture, Chunker, Encoders, Encoding, OutputWriter, Stream};
+use crate::recording::{self, Recorder};
+use crate::registry::CommandRegistry;
+use crate::runtime::{
+    CommandRecord, CommandState, ProcessId, ProcessIdResponse, RETURN_CODE_PANIC,
+    RETURN_CODE_TIMEOUT,
+};
 use crate::runtime::{Runtime, RuntimeControl, RuntimeDef};
+use crate::scheduler::{Scheduler, Ticket};
 use crate::serialize::json;
-use crate::RuntimeMode;
+use crate::state::StateStore;
+use crate::trace;
+use crate::volumes::Volumes;
+use crate::{RunProcess, RuntimeMode};
 
 /// Runtime execution context
 pub struct Context<R: Runtime + ?Sized> {
@@ -36,10 +53,33 @@ pub struct Context<R: Runtime + ?Sized> {
     /// and
     /// `command != Command::Deploy`
     pub emitter: Option<EventEmitter>,
+    /// Emit a RuntimeState "error" event when the runtime panics
+    /// outside of a command or fails to start, prior to terminating
+    pub report_panics: bool,
+    /// Volumes declared by the runtime
+    pub volumes: Volumes,
+    /// Default command execution timeout, e.g. read from `conf` in the `build` factory.
+    /// Overridden by `Runtime::command_timeout` for commands started with `Context::command_for`
+    pub command_timeout: Option<Duration>,
+    /// Concurrent command execution limits
+    pub scheduler: Scheduler,
+    /// Resource limits of processes spawned by commands
+    pub limits: Limits,
+    /// Isolation of processes spawned by commands
+    #[cfg(feature = "sandbox")]
+    pub sandbox: Option<crate::sandbox::SandboxPolicy>,
     /// Process ID sequence
     pid_seq: AtomicU64,
+    /// Runtime checkpoint handlers
+    checkpoints: Option<Checkpoints<R>>,
+    /// Runtime API session recorder
+    pub(crate) recorder: Option<Recorder>,
+    /// Command execution records
+    pub(crate) registry: CommandRegistry,
     /// Runtime control
     pub(crate) control: RuntimeControl,
+    /// Standard output writer
+    pub(crate) output: OutputWriter,
 }
 
 impl<R> Context<R>
@@ -69,6 +109,11 @@ where
         } else {
             Default::default()
         };
+        let volumes = match cli.workdir() {
+            Some(workdir) => Volumes::load(&StateStore::new(workdir)),
+            None => Default::default(),
+        };
+        let output = OutputWriter::new(env.stdout(), cli.pretty());
 
         Ok(Self {
             cli,
@@ -76,8 +121,19 @@ where
             conf_path,
             env: Box::new(env),
             emitter: None,
+            report_panics: false,
+            volumes,
+            command_timeout: None,
+            scheduler: Default::default(),
+            limits: Default::default(),
+            #[cfg(feature = "sandbox")]
+            sandbox: None,
             pid_seq: Default::default(),
+            checkpoints: None,
+            recorder: None,
+            registry: Default::default(),
             control: Default::default(),
+            output,
         })
     }
 
@@ -134,6 +190,48 @@ where
         self.control.clone()
     }
 
+    /// Resolve a path inside the runtime, e.g. `/in/file`, to a path within one of the volume
+    /// directories in the working directory
+    pub fn resolve_path(&self, path: impl AsRef<str>) -> Result<PathBuf, Error> {
+        let workdir = self
+            .cli
+            .workdir()
+            .ok_or_else(|| Error::invalid_argument("Working directory not set"))?;
+        self.volumes.resolve(workdir, path)
+    }
+
+    /// Create volume directories and persist volume declarations for subsequent invocations.
+    /// Returns the `vols` deployment property
+    pub(crate) fn deploy_volumes(&self) -> Result<json::Value, Error> {
+        if let Some(workdir) = self.cli.workdir() {
+            self.volumes.create(&workdir)?;
+            self.volumes.save(&StateStore::new(workdir))?;
+        } else if !self.volumes.is_empty() {
+            return Err(Error::invalid_argument("Working directory not set"));
+        }
+        json::to_value(&self.volumes).map_err(|e| Error::internal(e.to_string()))
+    }
+
+    /// Record Runtime API requests, responses and events in `RuntimeMode::Server`
+    /// to a JSON-lines file in the working directory.
+    /// Must be called prior to starting the runtime, e.g. in the `build` factory
+    pub fn enable_recording(&mut self) -> Result<(), Error> {
+        let workdir = self
+            .cli
+            .workdir()
+            .ok_or_else(|| Error::invalid_argument("Working directory not set"))?;
+        self.recorder = Some(Recorder::open(recording::path(workdir))?);
+        Ok(())
+    }
+
+    /// Return a persistent state store, rooted in the working directory
+    pub fn state_store(&self) -> Result<StateStore, Error> {
+        self.cli
+            .workdir()
+            .map(StateStore::new)
+            .ok_or_else(|| Error::invalid_argument("Working directory not set"))
+    }
+
     fn config_path<P: AsRef<Path>>(dir: P, name: &str) -> anyhow::Result<PathBuf> {
         let dir = dir.as_ref();
         let candidates = Self::CONF_EXTENSIONS
@@ -149,22 +247,111 @@ where
         Ok(conf_path)
     }
 
-    pub(crate) fn next_run_ctx(&self) -> RunCommandContext {
-        let id = self.pid_seq.fetch_add(1, Relaxed);
+    /// Return a persisted command execution record. Available in `RuntimeMode::Command`
+    pub fn command_record(&self, pid: ProcessId) -> Result<Option<CommandRecord>, Error> {
+        match self.command_store() {
+            Some(store) => store.get(&command_key(pid)),
+            None => Ok(None),
+        }
+    }
+
+    /// Return the execution record of a command started by this runtime.
+    /// In `RuntimeMode::Command`, records of previous invocations are read from the state store
+    pub fn command_status(&self, pid: ProcessId) -> Result<Option<CommandRecord>, Error> {
+        match self.registry.get(pid) {
+            Some(record) => Ok(Some(record)),
+            None => self.command_record(pid),
+        }
+    }
+
+    /// Return execution records of commands started by this runtime, ordered by process id.
+    /// Records of finished commands are retained up to a limit
+    pub fn commands(&self) -> Result<Vec<CommandRecord>, Error> {
+        let mut records = self.registry.list();
+        if let Some(store) = self.command_store() {
+            for key in store.keys()? {
+                let pid = match key
+                    .strip_prefix(COMMAND_KEY_PREFIX)
+                    .map(str::parse::<ProcessId>)
+                {
+                    Some(Ok(pid)) => pid,
+                    _ => continue,
+                };
+                if records.iter().any(|r| r.pid == pid) {
+                    continue;
+                }
+                records.extend(store.get::<CommandRecord>(&key)?);
+            }
+            records.sort_by_key(|r| r.pid);
+        }
+        Ok(records)
+    }
+
+    /// State store for process ids and command records, shared between
+    /// invocations of a `RuntimeMode::Command` runtime
+    fn command_store(&self) -> Option<StateStore> {
+        match R::MODE {
+            RuntimeMode::Command => self.state_store().ok(),
+            RuntimeMode::Server => None,
+        }
+    }
+
+    /// Allocate a process id and capture execution hints of `command`, if provided
+    pub(crate) fn next_run_ctx(&self, command: Option<&RunProcess>) -> RunCommandContext {
+        let store = self.command_store();
+        let id = match store.as_ref().map(next_pid) {
+            Some(Ok(id)) => {
+                self.pid_seq.store(id + 1, Relaxed);
+                id
+            }
+            Some(Err(_e)) => {
+                #[cfg(feature = "logger")]
+                log::warn!("Unable to persist the process id sequence: {}", _e);
+                self.pid_seq.fetch_add(1, Relaxed)
+            }
+            None => self.pid_seq.fetch_add(1, Relaxed),
+        };
         RunCommandContext {
             id,
+            name: command.map(|command| command.bin.clone()),
             emitter: self.emitter.clone(),
             control: self.control.clone(),
+            store,
+            timeout: match command {
+                Some(command) => R::command_timeout(command, self),
+                None => self.command_timeout,
+            },
+            scheduler: self.scheduler.clone(),
+            stdout_capture: capture(command.and_then(|c| c.stdout.as_ref())),
+            stderr_capture: capture(command.and_then(|c| c.stderr.as_ref())),
+            children: Default::default(),
+            return_code: Default::default(),
+            encoders: Default::default(),
+            output: self.output.clone(),
+            registry: self.registry.clone(),
+            limits: self.limits.clone(),
+            #[cfg(feature = "sandbox")]
+            sandbox: self.sandbox.clone(),
+            #[cfg(feature = "logger")]
+            log: crate::logger::CommandLog::new(id),
         }
     }
 
     pub(crate) fn set_emitter(&mut self, emitter: impl RuntimeHandler + Send + Sync + 'static) {
-        self.emitter.replace(EventEmitter::spawn(emitter));
+        let emitter = match self.recorder {
+            Some(ref recorder) => EventEmitter::spawn(recorder.handler(emitter)),
+            None => EventEmitter::spawn(emitter),
+        };
+        self.emitter.replace(emitter);
     }
 
     pub(crate) fn set_shutdown_tx(&mut self, tx: oneshot::Sender<()>) {
         self.control.shutdown_tx = Rc::new(RefCell::new(Some(tx)));
     }
+
+    pub(crate) fn set_failure_tx(&mut self, tx: oneshot::Sender<Error>) {
+        self.control.failure_tx = Rc::new(RefCell::new(Some(tx)));
+    }
 }
 
 impl<R> Context<R>
@@ -172,17 +359,53 @@ where
     R: Runtime + ?Sized,
     <R as RuntimeDef>::Cli: 'static,
 {
+    /// Execute a command handler with the default timeout and output capture modes
     pub fn command<'a, H, T, Fut>(&mut self, handler: H) -> ProcessIdResponse<'a>
     where
         H: (FnOnce(RunCommandContext) -> Fut) + 'static,
         T: Serialize,
         Fut: Future<Output = Result<T, Error>> + 'a,
     {
-        let run_ctx = self.next_run_ctx();
-        run_command(run_ctx, move |run_ctx| {
+        self.run_handler(None, handler)
+    }
+
+    /// Execute a handler of the `command` request, applying the `Runtime::command_timeout`
+    /// and `Runtime::command_priority` hints and the requested output capture modes
+    pub fn command_for<'a, H, T, Fut>(
+        &mut self,
+        command: &RunProcess,
+        handler: H,
+    ) -> ProcessIdResponse<'a>
+    where
+        H: (FnOnce(RunCommandContext) -> Fut) + 'static,
+        T: Serialize,
+        Fut: Future<Output = Result<T, Error>> + 'a,
+    {
+        self.run_handler(Some(command), handler)
+    }
+
+    fn run_handler<'a, H, T, Fut>(
+        &mut self,
+        command: Option<&RunProcess>,
+        handler: H,
+    ) -> ProcessIdResponse<'a>
+    where
+        H: (FnOnce(RunCommandContext) -> Fut) + 'static,
+        T: Serialize,
+        Fut: Future<Output = Result<T, Error>> + 'a,
+    {
+        let priority = command
+            .map(|command| R::command_priority(command, self))
+            .unwrap_or_default();
+        // rejected commands are not assigned a process id
+        let ticket = match self.scheduler.schedule(priority) {
+            Ok(ticket) => ticket,
+            Err(err) => return futures::future::err(err).boxed_local(),
+        };
+        let run_ctx = self.next_run_ctx(command);
+        run_command(run_ctx, ticket, move |run_ctx| {
             async move {
-                let id = run_ctx.id;
-                let emitter = run_ctx.emitter.clone();
+                let mut out_ctx = run_ctx.clone();
                 let output = handler(run_ctx).await?;
                 let value = json::to_value(&output).map_err(Error::from_string)?;
 
@@ -192,10 +415,11 @@ where
 
                 match R::MODE {
                     RuntimeMode::Command => {
-                        let _ = write_output(value).await;
+                        out_ctx.capture(value.to_string().as_bytes(), &[]);
+                        out_ctx.output.json(&value)?;
                     }
-                    RuntimeMode::Server if emitter.is_some() => {
-                        emitter.unwrap().command_stdout(id, value.to_string()).await;
+                    RuntimeMode::Server if out_ctx.emitter.is_some() => {
+                        out_ctx.stdout(value.to_string()).await;
                     }
                     RuntimeMode::Server => (),
                 }
@@ -204,14 +428,94 @@ where
             .boxed_local()
         })
     }
+
+    /// Parse `command` into one of `C` commands and execute it with `Context::command_for`.
+    /// Parsing errors are returned prior to emitting any command lifecycle events.
+    pub fn dispatch<'a, C, H, T, Fut>(
+        &mut self,
+        command: &RunProcess,
+        handler: H,
+    ) -> ProcessIdResponse<'a>
+    where
+        C: RuntimeCommands,
+        H: (FnOnce(C, RunCommandContext) -> Fut) + 'static,
+        T: Serialize,
+        Fut: Future<Output = Result<T, Error>> + 'a,
+    {
+        match C::from_run_process(command) {
+            Ok(cmd) => self.command_for(command, move |run_ctx| handler(cmd, run_ctx)),
+            Err(err) => futures::future::err(err).boxed_local(),
+        }
+    }
+}
+
+impl<R: Runtime + ?Sized> Context<R> {
+    pub(crate) fn checkpoints(&self) -> Option<Checkpoints<R>> {
+        self.checkpoints
+    }
+}
+
+impl<R> Context<R>
+where
+    R: Checkpoint,
+    <R as RuntimeDef>::Cli: 'static,
+{
+    /// Enable periodic and on-stop runtime checkpoints in `RuntimeMode::Server`.
+    /// Must be called prior to starting the runtime, e.g. in the `build` factory
+    pub fn enable_checkpoints(&mut self) {
+        self.checkpoints = Some(Checkpoints {
+            interval: R::INTERVAL,
+            save: Self::save_checkpoint,
+            restore: Self::restore_checkpoint,
+        });
+    }
+
+    /// Write a runtime snapshot to the working directory
+    pub fn save_checkpoint(&self, runtime: &R) -> Result<(), Error> {
+        let snapshot = Snapshot {
+            pid_seq: self.pid_seq.load(Relaxed),
+            runtime: json::to_value(runtime.snapshot()).map_err(Error::from_string)?,
+        };
+        self.state_store()?.set(Snapshot::KEY, &snapshot)
+    }
+
+    /// Restore the runtime and process ID sequence from the latest snapshot.
+    /// Returns whether a snapshot was found
+    pub fn restore_checkpoint(&mut self, runtime: &mut R) -> Result<bool, Error> {
+        let snapshot: Snapshot = match self.state_store()?.get(Snapshot::KEY)? {
+            Some(snapshot) => snapshot,
+            None => return Ok(false),
+        };
+        let state = json::from_value(snapshot.runtime)
+            .map_err(|e| Error::internal(format!("Invalid checkpoint: {}", e)))?;
+        runtime.restore(state)?;
+        self.pid_seq.store(snapshot.pid_seq, Relaxed);
+        Ok(true)
+    }
 }
 
 /// Command execution handler
 #[derive(Clone)]
 pub struct RunCommandContext {
     pub(crate) id: ProcessId,
+    pub(crate) name: Option<String>,
     pub(crate) emitter: Option<EventEmitter>,
     pub(crate) control: RuntimeControl,
+    pub(crate) store: Option<StateStore>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) scheduler: Scheduler,
+    pub(crate) stdout_capture: Capture,
+    pub(crate) stderr_capture: Capture,
+    pub(crate) children: Children,
+    pub(crate) return_code: ReturnCode,
+    pub(crate) encoders: Encoders,
+    pub(crate) output: OutputWriter,
+    pub(crate) registry: CommandRegistry,
+    pub(crate) limits: Limits,
+    #[cfg(feature = "sandbox")]
+    pub(crate) sandbox: Option<crate::sandbox::SandboxPolicy>,
+    #[cfg(feature = "logger")]
+    pub(crate) log: crate::logger::CommandLog,
 }
 
 impl RunCommandContext {
@@ -222,6 +526,7 @@ impl RunCommandContext {
 
     pub(crate) fn started(&mut self) -> BoxFuture<()> {
         let id = self.id;
+        self.record(CommandState::Running, None);
         self.emitter
             .as_mut()
             .map(|e| e.command_started(id))
@@ -230,32 +535,62 @@ impl RunCommandContext {
 
     pub(crate) fn stopped(&mut self, return_code: i32) -> BoxFuture<()> {
         let id = self.id;
+        self.record(CommandState::Finished, Some(return_code));
         self.emitter
             .as_mut()
             .map(|e| e.command_stopped(id, return_code))
             .unwrap_or_else(|| futures::future::ready(()).boxed())
     }
 
-    /// Emit a RUN command output event (stdout)
+    /// Emit a RUN command output event (stdout), encoded with the stdout encoding
     pub fn stdout(&mut self, output: impl IntoVec<u8>) -> BoxFuture<()> {
-        let id = self.id;
-        let output = output.into_vec();
-        match self.emitter {
-            Some(ref mut e) => e.command_stdout(id, output),
-            None => Self::print_output(output),
-        }
+        let output = self.encoders.encode(Stream::Stdout, output.into_vec());
+        self.emit(Stream::Stdout, output)
     }
 
-    /// Emit a RUN command output event (stderr)
+    /// Emit a RUN command output event (stderr), encoded with the stderr encoding
     pub fn stderr(&mut self, output: impl IntoVec<u8>) -> BoxFuture<()> {
-        let id = self.id;
-        let output = output.into_vec();
-        match self.emitter {
-            Some(ref mut e) => e.command_stderr(id, output),
-            None => Self::print_output(output),
+        let output = self.encoders.encode(Stream::Stderr, output.into_vec());
+        self.emit(Stream::Stderr, output)
+    }
+
+    /// Set the encoding of both stdout and stderr output. Defaults to `Encoding::Raw`
+    pub fn set_encoding(&self, encoding: Encoding) {
+        self.set_stdout_encoding(encoding);
+        self.set_stderr_encoding(encoding);
+    }
+
+    /// Set the encoding of stdout output
+    pub fn set_stdout_encoding(&self, encoding: Encoding) {
+        self.encoders.set(Stream::Stdout, encoding);
+    }
+
+    /// Set the encoding of stderr output
+    pub fn set_stderr_encoding(&self, encoding: Encoding) {
+        self.encoders.set(Stream::Stderr, encoding);
+    }
+
+    /// Emit output held back by the encodings, e.g. an unterminated line.
+    /// Called automatically when the command finishes
+    pub async fn flush(&mut self) {
+        for stream in [Stream::Stdout, Stream::Stderr] {
+            let output = self.encoders.finish(stream);
+            self.emit(stream, output).await;
         }
     }
 
+    /// Read `reader` until the end of stream and emit its contents as stdout output events,
+    /// according to the capture mode requested in `RunProcess`
+    pub async fn pipe_stdout(&self, reader: impl AsyncRead + Unpin) -> Result<(), Error> {
+        self.pipe(reader, Stream::Stdout).await
+    }
+
+    /// Read `reader` until the end of stream and emit its contents as stderr output events,
+    /// according to the capture mode requested in `RunProcess`
+    pub async fn pipe_stderr(&self, reader: impl AsyncRead + Unpin) -> Result<(), Error> {
+        self.pipe(reader, Stream::Stderr).await
+    }
+
     /// Emit a STATE event
     pub fn state(&mut self, name: String, value: json::Value) -> BoxFuture<Result<(), Error>> {
         match self.emitter {
@@ -277,6 +612,15 @@ impl RunCommandContext {
         }
     }
 
+    /// Emit a STATE event with the current command queue depth
+    fn queue_depth(&mut self) -> BoxFuture<()> {
+        let depth = self.scheduler.depth();
+        let value = json::to_value(depth).unwrap_or_default();
+        self.state(Scheduler::STATE.to_string(), value)
+            .map(|_| ())
+            .boxed()
+    }
+
     /// Emit a COUNTER event
     pub fn counter(&mut self, name: String, value: f64) -> BoxFuture<()> {
         match self.emitter {
@@ -290,10 +634,126 @@ impl RunCommandContext {
         self.control.clone()
     }
 
-    fn print_output<'a>(output: impl IntoVec<u8>) -> BoxFuture<'a, ()> {
-        let mut stdout = std::io::stdout();
-        let _ = stdout.write_all(output.into_vec().as_slice());
-        let _ = stdout.flush();
+    /// Get command execution timeout
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Spawn a child process, constrained by `Context::limits`
+    /// and isolated according to `Context::sandbox`.
+    /// Unless waited for or dropped by then, the process is killed when the command times out
+    pub fn spawn(&self, command: tokio::process::Command) -> Result<LimitedChild, Error> {
+        crate::limits::spawn(
+            command,
+            &self.limits,
+            self.id,
+            &self.children,
+            &self.return_code,
+            #[cfg(feature = "sandbox")]
+            self.sandbox.as_ref(),
+        )
+    }
+
+    /// Set the return code reported when the command stops. Defaults to 0 for handlers
+    /// returning `Ok` and to 1 for errors. Waiting for a process with `LimitedChild::wait`
+    /// sets its exit code
+    pub fn set_return_code(&self, code: i32) {
+        self.return_code.set(code);
+    }
+
+    /// Write log records emitted by this command to a separate file
+    #[cfg(feature = "logger")]
+    pub fn log_to_file(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        Ok(self.log.set_file(path.as_ref())?)
+    }
+
+    /// Forward log records of this command, up to the specified level,
+    /// to the requestor as stderr output events.
+    /// Has no effect without an event emitter, i.e. in `RuntimeMode::Command`, where the logger
+    /// already duplicates records up to the configured log level to the process stderr
+    #[cfg(feature = "logger")]
+    pub fn log_to_stderr(&mut self, level: log::LevelFilter) {
+        match self.emitter.clone() {
+            Some(emitter) => self.log.set_stderr(level, emitter),
+            None => {
+                static WARN: std::sync::Once = std::sync::Once::new();
+                WARN.call_once(|| {
+                    log::warn!(
+                        "Log records cannot be forwarded as stderr events without an emitter"
+                    )
+                });
+            }
+        }
+    }
+
+    async fn pipe(&self, mut reader: impl AsyncRead + Unpin, stream: Stream) -> Result<(), Error> {
+        let mut ctx = self.clone();
+        let mut chunker = Chunker::new(match stream {
+            Stream::Stdout => self.stdout_capture.clone(),
+            Stream::Stderr => self.stderr_capture.clone(),
+        });
+        let mut buf = vec![0u8; PIPE_BUFFER_SIZE];
+
+        loop {
+            let n = reader.read(&mut buf).await?;
+            let chunks = match n {
+                0 => chunker.finish().into_iter().collect(),
+                _ => chunker.push(&buf[..n]),
+            };
+            for chunk in chunks {
+                match stream {
+                    Stream::Stdout => ctx.stdout(chunk).await,
+                    Stream::Stderr => ctx.stderr(chunk).await,
+                }
+            }
+            if n == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn emit(&mut self, stream: Stream, output: Option<Vec<u8>>) -> BoxFuture<()> {
+        let id = self.id;
+        let output = match output {
+            Some(output) => output,
+            None => return futures::future::ready(()).boxed(),
+        };
+        match stream {
+            Stream::Stdout => self.capture(&output, &[]),
+            Stream::Stderr => self.capture(&[], &output),
+        }
+        match (self.emitter.as_mut(), stream) {
+            (Some(e), Stream::Stdout) => e.command_stdout(id, output),
+            (Some(e), Stream::Stderr) => e.command_stderr(id, output),
+            (None, _) => Self::print_output(output),
+        }
+    }
+
+    fn capture(&self, stdout: &[u8], stderr: &[u8]) {
+        self.registry.output(self.id, stdout, stderr);
+    }
+
+    fn record(&self, state: CommandState, return_code: Option<i32>) {
+        let record = self.registry.set_state(self.id, state, return_code);
+        if let Some(ref store) = self.store {
+            if let Err(_e) = store.set(&command_key(self.id), &record) {
+                #[cfg(feature = "logger")]
+                log::warn!("Unable to persist the command record: {}", _e);
+            }
+            if state == CommandState::Finished {
+                if let Err(_e) = prune_records(store) {
+                    #[cfg(feature = "logger")]
+                    log::warn!("Unable to prune command records: {}", _e);
+                }
+            }
+        }
+    }
+
+    /// Print output of a command invoked via command line to stderr,
+    /// leaving stdout to the JSON result or error of the command
+    fn print_output<'a>(output: Vec<u8>) -> BoxFuture<'a, ()> {
+        let mut stderr = std::io::stderr();
+        let _ = stderr.write_all(&output).and_then(|_| stderr.flush());
         futures::future::ready(()).boxed()
     }
 }
@@ -315,6 +775,10 @@ pub trait RunCommandExt<R: Runtime + ?Sized> {
 
 /// Implements `RunCommandExt` for `Future`s outputting `Result`s.
 /// The output result is checked prior to emitting any command lifecycle events.
+///
+/// The future is awaited before the command is admitted by the `Scheduler` and is not
+/// subject to the command timeout. Processes should be spawned in `handler` instead,
+/// with `RunCommandContext::spawn`, so that they are limited, queued and killed on timeout.
 impl<R, F, Rt, Re> RunCommandExt<R> for F
 where
     R: Runtime + ?Sized,
@@ -331,10 +795,14 @@ where
         H: (FnOnce(Self::Item, RunCommandContext) -> Fh) + 'static,
         Fh: Future<Output = Result<(), Error>> + 'static,
     {
-        let run_ctx = ctx.next_run_ctx();
+        let ticket = match ctx.scheduler.schedule(0) {
+            Ok(ticket) => ticket,
+            Err(err) => return futures::future::err(err).boxed_local(),
+        };
+        let run_ctx = ctx.next_run_ctx(None);
         async move {
             let value = self.await?;
-            run_command(run_ctx, move |run_ctx| async move {
+            run_command(run_ctx, ticket, move |run_ctx| async move {
                 handler(value, run_ctx).await
             })
             .await
@@ -343,26 +811,143 @@ where
     }
 }
 
-fn run_command<'a, H, F>(mut run_ctx: RunCommandContext, handler: H) -> ProcessIdResponse<'a>
+fn run_command<'a, H, F>(
+    mut run_ctx: RunCommandContext,
+    ticket: Ticket,
+    handler: H,
+) -> ProcessIdResponse<'a>
 where
     H: (FnOnce(RunCommandContext) -> F) + 'static,
     F: Future<Output = Result<(), Error>> + 'static,
 {
-    async move {
-        let pid = run_ctx.id;
-        run_ctx.started().await;
+    let pid = run_ctx.id;
+    let timeout = run_ctx.timeout;
+    let name = run_ctx.name.clone();
+    trace::command(pid, name.as_deref(), async move {
+        let queued = ticket.is_queued();
+        match queued {
+            true => {
+                run_ctx.record(CommandState::Queued, None);
+                run_ctx.queue_depth().await;
+            }
+            false => run_ctx.started().await,
+        }
 
-        let fut = handler(run_ctx.clone());
-        tokio::task::spawn_local(async move {
-            let return_code = fut.await.is_err() as i32;
+        let lifecycle = async move {
+            let _permit = match ticket.ready().await {
+                Some(permit) => permit,
+                None => {
+                    // the scheduler has been dropped with the command still queued
+                    let message = "Command cancelled";
+                    run_ctx.stderr(format!("{}\n", message)).await;
+                    run_ctx.flush().await;
+                    run_ctx.stopped(1).await;
+                    run_ctx.registry.finish(pid, Some(Error::internal(message)));
+                    return;
+                }
+            };
+            if queued {
+                run_ctx.queue_depth().await;
+                run_ctx.started().await;
+            }
+            #[cfg(feature = "tracing")]
+            tracing::debug!("command started");
+
+            let fut = handler(run_ctx.clone());
+            #[cfg(feature = "logger")]
+            let fut = run_ctx.log.clone().scope(fut);
+
+            let fut = AssertUnwindSafe(fut).catch_unwind();
+            futures::pin_mut!(fut);
+            let result = match timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, fut.as_mut()).await {
+                    Ok(result) => Some(result),
+                    Err(_) => {
+                        // processes are killed while still owned by the handler,
+                        // so that their ids cannot be reused by then
+                        run_ctx.children.kill();
+                        None
+                    }
+                },
+                None => Some(fut.await),
+            };
+            let reported = run_ctx.return_code.get();
+            let (return_code, error) = match result {
+                Some(Ok(Ok(()))) => (reported.unwrap_or(0), None),
+                Some(Ok(Err(err))) => (reported.filter(|code| *code != 0).unwrap_or(1), Some(err)),
+                Some(Err(payload)) => {
+                    let message = format!("Command panicked: {}", panic_message(payload.as_ref()));
+                    run_ctx.stderr(format!("{}\n", message)).await;
+                    (RETURN_CODE_PANIC, Some(Error::internal(message)))
+                }
+                None => {
+                    let timeout = timeout.unwrap_or_default();
+                    let message = format!("Command timed out after {:?}", timeout);
+                    run_ctx.stderr(format!("{}\n", message)).await;
+                    (RETURN_CODE_TIMEOUT, Some(Error::timeout(message)))
+                }
+            };
+            run_ctx.flush().await;
+            #[cfg(feature = "tracing")]
+            tracing::debug!(return_code, "command stopped");
             run_ctx.stopped(return_code).await;
-        });
+            run_ctx.registry.finish(pid, error);
+        };
+        tokio::task::spawn_local(trace::current(lifecycle));
 
         Ok(pid)
-    }
+    })
     .boxed_local()
 }
 
+/// Output capture mode requested for a stream, if any
+fn capture(output: Option<&ya_runtime_api::server::proto::Output>) -> Capture {
+    output.map(Capture::from).unwrap_or_default()
+}
+
+const PIPE_BUFFER_SIZE: usize = 8192;
+const PID_SEQ_KEY: &str = "ya-runtime-sdk.pid-seq";
+
+const COMMAND_KEY_PREFIX: &str = "ya-runtime-sdk.command.";
+
+fn command_key(pid: ProcessId) -> String {
+    format!("{}{}", COMMAND_KEY_PREFIX, pid)
+}
+
+/// Allocate the next process id from a persisted sequence,
+/// locked against concurrent invocations of the runtime
+fn next_pid(store: &StateStore) -> Result<ProcessId, Error> {
+    let _lock = store.lock(PID_SEQ_KEY)?;
+    let next = store.update(PID_SEQ_KEY, |next: Option<ProcessId>| {
+        next.unwrap_or_default() + 1
+    })?;
+    Ok(next - 1)
+}
+
+/// Remove persisted records of the earliest finished commands,
+/// retaining as many as `CommandRegistry` does in memory
+fn prune_records(store: &StateStore) -> Result<(), Error> {
+    let mut pids = store
+        .keys()?
+        .iter()
+        .filter_map(|key| key.strip_prefix(COMMAND_KEY_PREFIX)?.parse().ok())
+        .collect::<Vec<ProcessId>>();
+    pids.sort_unstable();
+
+    let mut excess = pids.len().saturating_sub(CommandRegistry::MAX_FINISHED);
+    for pid in pids {
+        if excess == 0 {
+            break;
+        }
+        let key = command_key(pid);
+        if let Some(CommandState::Finished) = store.get::<CommandRecord>(&key)?.map(|r| r.state) {
+            store.remove(&key)?;
+            excess -= 1;
+        }
+    }
+    Ok(())
+}
+
 fn file_extension<P: AsRef<Path>>(path: P) -> anyhow::Result<String> {
     Ok(path
         .as_ref()
@@ -0,0 +1,292 @@
+//! Supervisor-side debugging client for `RuntimeMode::Server` runtimes
+use std::io::Write;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use ya_runtime_api::server::proto::output::Type;
+use ya_runtime_api::server::proto::response::create_network::Endpoint;
+use ya_runtime_api::server::proto::Output;
+use ya_runtime_api::server::{
+    CreateNetwork, ErrorResponse, KillProcess, Network, NetworkInterface, ProcessStatus,
+    RunProcess, RuntimeService, RuntimeStatus, RuntimeStatusKind,
+};
+
+use crate::error::Error;
+
+const OUTPUT_BUFFER_SIZE: u64 = 16384;
+const DEFAULT_SIGNAL: i32 = 15;
+
+const HELP: &str = "\
+run <bin> [args..]       start a command
+kill <pid> [signal]      stop a command
+network [key=value..]    create a network; keys: addr, mask, gateway, if_addr,
+                         interface (vpn | inet), host.<ip> (hostname)
+shutdown                 shut down the runtime and exit
+help                     print this message";
+
+/// Request issued from the debugger prompt
+#[derive(Clone, Debug, PartialEq)]
+pub enum DebugCommand {
+    Run(RunProcess),
+    Kill(KillProcess),
+    Network(CreateNetwork),
+    Shutdown,
+    Help,
+}
+
+impl DebugCommand {
+    /// Parse a prompt line. Returns `None` for empty lines
+    pub fn parse(line: &str, work_dir: &str) -> Result<Option<Self>, Error> {
+        let mut args = split(line)?;
+        if args.is_empty() {
+            return Ok(None);
+        }
+        let command = args.remove(0);
+
+        let command = match command.as_str() {
+            "run" => {
+                if args.is_empty() {
+                    return Err(Error::invalid_argument("Missing binary"));
+                }
+                let bin = args.remove(0);
+                let output = Some(Output {
+                    r#type: Some(Type::Stream(OUTPUT_BUFFER_SIZE)),
+                });
+                Self::Run(RunProcess {
+                    bin,
+                    args,
+                    work_dir: work_dir.to_string(),
+                    stdout: output.clone(),
+                    stderr: output,
+                })
+            }
+            "kill" => {
+                let pid = args
+                    .first()
+                    .ok_or_else(|| Error::invalid_argument("Missing process id"))?;
+                let signal = args.get(1).map(String::as_str);
+                Self::Kill(KillProcess {
+                    pid: parse_arg("pid", pid)?,
+                    signal: signal
+                        .map(|s| parse_arg("signal", s))
+                        .transpose()?
+                        .unwrap_or(DEFAULT_SIGNAL),
+                })
+            }
+            "network" => Self::Network(parse_network(args)?),
+            "shutdown" | "exit" | "quit" => Self::Shutdown,
+            "help" | "?" => Self::Help,
+            _ => {
+                return Err(
+                    Error::invalid_argument("Unknown command").with_context("command", command)
+                )
+            }
+        };
+        Ok(Some(command))
+    }
+}
+
+/// Read commands from `input` and forward them to the runtime, until a `shutdown`
+/// command is issued or the input is closed
+pub async fn repl<S, R, W>(
+    service: &S,
+    mut input: R,
+    output: &mut W,
+    work_dir: &str,
+) -> anyhow::Result<()>
+where
+    S: RuntimeService + ?Sized,
+    R: AsyncBufRead + Unpin,
+    W: Write,
+{
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line).await? == 0 {
+            writeln!(output)?;
+            break;
+        }
+
+        let command = match DebugCommand::parse(&line, work_dir) {
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(err) => {
+                writeln!(output, "error: {}", format_error(&err))?;
+                continue;
+            }
+        };
+
+        let result = match command {
+            DebugCommand::Run(run) => service
+                .run_process(run)
+                .await
+                .map(|resp| format!("pid: {}", resp.pid)),
+            DebugCommand::Kill(kill) => service.kill_process(kill).await.map(|_| "ok".to_string()),
+            DebugCommand::Network(network) => {
+                service
+                    .create_network(network)
+                    .await
+                    .map(|resp| match resp.endpoint {
+                        Some(endpoint) => format!("endpoint: {}", format_endpoint(&endpoint)),
+                        None => "ok".to_string(),
+                    })
+            }
+            DebugCommand::Help => Ok(HELP.to_string()),
+            DebugCommand::Shutdown => break,
+        };
+
+        match result {
+            Ok(message) => writeln!(output, "{}", message)?,
+            Err(err) => writeln!(output, "error: {}", format_error(&Error::from(err)))?,
+        }
+    }
+
+    service
+        .shutdown()
+        .await
+        .map_err(|e: ErrorResponse| anyhow::anyhow!(format_error(&Error::from(e))))
+}
+
+/// Format a process status event
+pub fn format_process_status(status: &ProcessStatus) -> String {
+    let mut lines = Vec::new();
+    for (stream, bytes) in [("stdout", &status.stdout), ("stderr", &status.stderr)] {
+        let text = String::from_utf8_lossy(bytes);
+        lines.extend(
+            text.lines()
+                .map(|l| format!("[{}] {}: {}", status.pid, stream, l)),
+        );
+    }
+    if !status.running {
+        lines.push(format!(
+            "[{}] exited with code {}",
+            status.pid, status.return_code
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Format a runtime status event
+pub fn format_runtime_status(status: &RuntimeStatus) -> String {
+    match status.kind {
+        Some(RuntimeStatusKind::State(ref state)) => format!(
+            "[runtime] state {}: {}",
+            state.name,
+            String::from_utf8_lossy(&state.value)
+        ),
+        Some(RuntimeStatusKind::Counter(ref counter)) => {
+            format!("[runtime] counter {}: {}", counter.name, counter.value)
+        }
+        None => "[runtime] empty status".to_string(),
+    }
+}
+
+fn format_endpoint(endpoint: &Endpoint) -> String {
+    match endpoint {
+        Endpoint::UnixStream(s) => format!("unix://{}", s),
+        Endpoint::UnixDatagram(s) => format!("unixgram://{}", s),
+        Endpoint::UdpDatagram(s) => format!("udp://{}", s),
+        Endpoint::TcpStream(s) => format!("tcp://{}", s),
+    }
+}
+
+fn format_error(err: &Error) -> String {
+    let mut context = err
+        .context()
+        .iter()
+        .map(|(k, v)| format!("{}: {}", k, v))
+        .collect::<Vec<_>>();
+    context.sort();
+    match context.is_empty() {
+        true => format!("{} (code {})", err.message(), err.code()),
+        false => format!(
+            "{} (code {}; {})",
+            err.message(),
+            err.code(),
+            context.join(", ")
+        ),
+    }
+}
+
+fn parse_network(args: Vec<String>) -> Result<CreateNetwork, Error> {
+    let mut network = Network::default();
+    let mut create = CreateNetwork {
+        interface: NetworkInterface::Vpn as i32,
+        ..Default::default()
+    };
+
+    for arg in args {
+        let (key, value) = arg.split_once('=').ok_or_else(|| {
+            Error::invalid_argument("Expected a key=value pair").with_context("arg", &arg)
+        })?;
+        let value = value.to_string();
+        match key {
+            "addr" => network.addr = value,
+            "mask" => network.mask = value,
+            "gateway" => network.gateway = value,
+            "if_addr" => network.if_addr = value,
+            "interface" => {
+                create.interface = match value.as_str() {
+                    "vpn" => NetworkInterface::Vpn as i32,
+                    "inet" => NetworkInterface::Inet as i32,
+                    _ => {
+                        return Err(Error::invalid_argument("Invalid interface")
+                            .with_context("interface", value))
+                    }
+                }
+            }
+            _ => match key.strip_prefix("host.") {
+                Some(ip) => {
+                    create.hosts.insert(ip.to_string(), value);
+                }
+                None => return Err(Error::invalid_argument("Unknown key").with_context("key", key)),
+            },
+        }
+    }
+
+    if network.addr.is_empty() || network.mask.is_empty() {
+        return Err(Error::invalid_argument("Missing network addr or mask"));
+    }
+    create.networks.push(network);
+    Ok(create)
+}
+
+fn parse_arg<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::invalid_argument(format!("Invalid {}", name)).with_context(name, value))
+}
+
+/// Split a line into shell-like words, supporting quotes and backslash escapes
+fn split(line: &str) -> Result<Vec<String>, Error> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                let c = chars
+                    .next()
+                    .ok_or_else(|| Error::invalid_argument("Unterminated escape sequence"))?;
+                word.get_or_insert_with(String::new).push(c);
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(Error::invalid_argument("Unterminated quote"));
+    }
+    words.extend(word.take());
+    Ok(words)
+}
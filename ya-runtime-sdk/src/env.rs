@@ -1,4 +1,5 @@
 use crate::cli::{parse_cli, CommandCli};
+use std::io::Write;
 use std::path::PathBuf;
 
 /// Runtime environment configuration
@@ -15,11 +16,21 @@ pub trait Env<C: CommandCli> {
             .unwrap_or_else(|| PathBuf::from(runtime_name)))
     }
 
+    /// Directory to store the log files at
+    fn log_directory(&self, runtime_name: &str) -> anyhow::Result<PathBuf> {
+        Ok(self.data_directory(runtime_name)?.join("logs"))
+    }
+
     /// Command line arguments
     fn args(&self) -> Box<dyn Iterator<Item = String>> {
         Box::new(std::env::args())
     }
 
+    /// Standard output, receiving JSON results and errors
+    fn stdout(&self) -> Box<dyn Write> {
+        Box::new(std::io::stdout())
+    }
+
     /// Parse command line arguments
     fn cli(&mut self, project_name: &str, project_version: &str) -> anyhow::Result<C> {
         let name = self
@@ -31,6 +31,66 @@ where
     }
 }
 
+/// Well-known error kinds, mapped to stable error codes.
+/// Codes follow the values of corresponding POSIX `errno` constants.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    /// Invalid argument (`EINVAL`)
+    InvalidArgument,
+    /// Operation not supported (`EOPNOTSUPP`)
+    NotSupported,
+    /// Entity not found (`ENOENT`)
+    NotFound,
+    /// Operation timed out (`ETIMEDOUT`)
+    Timeout,
+    /// Resource busy, e.g. the command queue is full (`EBUSY`)
+    Busy,
+    /// Internal error
+    Internal,
+}
+
+impl ErrorKind {
+    /// Return the error code of this kind
+    pub const fn code(&self) -> i32 {
+        match self {
+            Self::InvalidArgument => 22,
+            Self::NotSupported => 95,
+            Self::NotFound => 2,
+            Self::Timeout => 110,
+            Self::Busy => 16,
+            Self::Internal => 1,
+        }
+    }
+
+    /// Resolve a well-known error kind from code
+    pub fn from_code(code: i32) -> Option<Self> {
+        [
+            Self::InvalidArgument,
+            Self::NotSupported,
+            Self::NotFound,
+            Self::Timeout,
+            Self::Busy,
+            Self::Internal,
+        ]
+        .iter()
+        .find(|kind| kind.code() == code)
+        .copied()
+    }
+}
+
+impl From<io::ErrorKind> for ErrorKind {
+    fn from(kind: io::ErrorKind) -> Self {
+        match kind {
+            io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => Self::InvalidArgument,
+            io::ErrorKind::Unsupported => Self::NotSupported,
+            io::ErrorKind::NotFound => Self::NotFound,
+            io::ErrorKind::TimedOut => Self::Timeout,
+            _ => Self::Internal,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct Error {
     code: i32,
@@ -39,6 +99,20 @@ pub struct Error {
 }
 
 impl Error {
+    /// Create a new error of a well-known kind
+    pub fn new(kind: ErrorKind, message: impl ToString) -> Self {
+        Self::from_code(kind.code(), message)
+    }
+
+    /// Create a new error with a custom code
+    pub fn from_code(code: i32, message: impl ToString) -> Self {
+        Error {
+            code,
+            message: message.to_string(),
+            context: Default::default(),
+        }
+    }
+
     pub fn response<'a, T: 'a>(s: impl ToString) -> LocalBoxFuture<'a, Result<T, Self>> {
         let err = Self::from(s.to_string());
         futures::future::err(err).boxed_local()
@@ -47,6 +121,62 @@ impl Error {
     pub fn from_string(s: impl ToString) -> Self {
         Self::from(s.to_string())
     }
+
+    /// Create a new `ErrorKind::InvalidArgument` error
+    pub fn invalid_argument(message: impl ToString) -> Self {
+        Self::new(ErrorKind::InvalidArgument, message)
+    }
+
+    /// Create a new `ErrorKind::NotSupported` error
+    pub fn not_supported(message: impl ToString) -> Self {
+        Self::new(ErrorKind::NotSupported, message)
+    }
+
+    /// Create a new `ErrorKind::NotFound` error
+    pub fn not_found(message: impl ToString) -> Self {
+        Self::new(ErrorKind::NotFound, message)
+    }
+
+    /// Create a new `ErrorKind::Timeout` error
+    pub fn timeout(message: impl ToString) -> Self {
+        Self::new(ErrorKind::Timeout, message)
+    }
+
+    /// Create a new `ErrorKind::Busy` error
+    pub fn busy(message: impl ToString) -> Self {
+        Self::new(ErrorKind::Busy, message)
+    }
+
+    /// Create a new `ErrorKind::Internal` error
+    pub fn internal(message: impl ToString) -> Self {
+        Self::new(ErrorKind::Internal, message)
+    }
+
+    /// Add a context entry
+    pub fn with_context(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.context.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Return the error code
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// Return the well-known error kind, if the code matches one
+    pub fn kind(&self) -> Option<ErrorKind> {
+        ErrorKind::from_code(self.code)
+    }
+
+    /// Return the error message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Return the error context
+    pub fn context(&self) -> &HashMap<String, String> {
+        &self.context
+    }
 }
 
 impl From<String> for Error {
@@ -61,17 +191,27 @@ impl From<String> for Error {
 
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
-        Error {
-            code: e.raw_os_error().unwrap_or(1),
-            message: e.to_string(),
-            context: Default::default(),
-        }
+        let code = e
+            .raw_os_error()
+            .unwrap_or_else(|| ErrorKind::from(e.kind()).code());
+        Self::from_code(code, e)
     }
 }
 
+/// Unwraps an `Error`, retaining its code and context. Other errors preserve
+/// their source chain in context, under `source.<n>` keys
 impl From<anyhow::Error> for Error {
     fn from(e: anyhow::Error) -> Self {
-        Self::from_string(e)
+        let e = match e.downcast::<Error>() {
+            Ok(err) => return err,
+            Err(e) => e,
+        };
+        e.chain()
+            .skip(1)
+            .enumerate()
+            .fold(Self::from_string(&e), |err, (i, source)| {
+                err.with_context(format!("source.{}", i), source)
+            })
     }
 }
 
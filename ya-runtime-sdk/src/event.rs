@@ -1,7 +1,9 @@
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
-use futures::channel::mpsc;
+use futures::channel::{mpsc, oneshot};
 use futures::future::BoxFuture;
 use futures::{FutureExt, SinkExt, StreamExt};
 
@@ -17,6 +19,28 @@ pub enum EventKind {
     Runtime(RuntimeStatus),
 }
 
+impl EventKind {
+    /// Return the type of this event. Empty runtime status events have no type
+    pub fn event_type(&self) -> Option<EventType> {
+        match self {
+            Self::Process(_) => Some(EventType::Process),
+            Self::Runtime(status) => match status.kind {
+                Some(RuntimeStatusKind::State(_)) => Some(EventType::State),
+                Some(RuntimeStatusKind::Counter(_)) => Some(EventType::Counter),
+                None => None,
+            },
+        }
+    }
+
+    /// Return the process id of a process event
+    pub fn pid(&self) -> Option<ProcessId> {
+        match self {
+            Self::Process(status) => Some(status.pid),
+            Self::Runtime(_) => None,
+        }
+    }
+}
+
 impl From<ProcessStatus> for EventKind {
     fn from(status: ProcessStatus) -> Self {
         Self::Process(status)
@@ -35,32 +59,165 @@ impl From<RuntimeStatusKind> for EventKind {
     }
 }
 
+/// Runtime event type
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EventType {
+    /// Command lifecycle and output events
+    Process,
+    /// Runtime state events
+    State,
+    /// Usage counter events
+    Counter,
+}
+
+/// Selects events delivered to an `EventEmitter` subscriber.
+/// The default filter accepts all events
+#[derive(Clone, Debug, Default)]
+pub struct EventFilter {
+    pids: Option<HashSet<ProcessId>>,
+    types: Option<HashSet<EventType>>,
+}
+
+impl EventFilter {
+    /// Accept events of the specified process. Can be called multiple times.
+    /// Runtime events, not related to any process, are rejected
+    pub fn pid(mut self, pid: ProcessId) -> Self {
+        self.pids.get_or_insert_with(Default::default).insert(pid);
+        self
+    }
+
+    /// Accept events of the specified type. Can be called multiple times
+    pub fn event_type(mut self, event_type: EventType) -> Self {
+        self.types
+            .get_or_insert_with(Default::default)
+            .insert(event_type);
+        self
+    }
+
+    /// Check whether the event is accepted by this filter
+    pub fn matches(&self, event: &EventKind) -> bool {
+        let pid = match (&self.pids, event.pid()) {
+            (None, _) => true,
+            (Some(pids), Some(pid)) => pids.contains(&pid),
+            (Some(_), None) => false,
+        };
+        let event_type = match (&self.types, event.event_type()) {
+            (None, _) => true,
+            (Some(types), Some(event_type)) => types.contains(&event_type),
+            (Some(_), None) => false,
+        };
+        pid && event_type
+    }
+}
+
+type Subscribers = Arc<Mutex<Vec<(EventFilter, mpsc::Sender<EventKind>)>>>;
+
+/// Message handled by the emitter tasks, in order
+enum Message<T> {
+    Event(T),
+    /// Acknowledged once all preceding events are handled
+    Flush(oneshot::Sender<()>),
+}
+
 /// Runtime event emitter
 #[derive(Clone)]
 pub struct EventEmitter {
-    tx_process: mpsc::Sender<ProcessStatus>,
-    tx_runtime: mpsc::Sender<RuntimeStatus>,
+    tx_process: mpsc::Sender<Message<ProcessStatus>>,
+    tx_runtime: mpsc::Sender<Message<RuntimeStatus>>,
+    subscribers: Subscribers,
 }
 
 impl EventEmitter {
+    /// Number of events buffered for each subscriber
+    pub const SUBSCRIBER_BUFFER: usize = 1024;
+
     pub fn spawn(emitter: impl RuntimeHandler + 'static) -> Self {
         let (tx_p, rx_p) = mpsc::channel(1);
         let (tx_r, rx_r) = mpsc::channel(1);
         let e_p = Rc::new(RefCell::new(emitter));
         let e_r = e_p.clone();
+        let subscribers = Subscribers::default();
+        let s_p = subscribers.clone();
+        let s_r = subscribers.clone();
 
-        tokio::task::spawn_local(
-            rx_p.for_each(move |status| e_p.borrow().on_process_status(status)),
-        );
-        tokio::task::spawn_local(
-            rx_r.for_each(move |status| e_r.borrow().on_runtime_status(status)),
-        );
+        tokio::task::spawn_local(rx_p.for_each(move |message| match message {
+            Message::Event(status) => {
+                let broadcast = Broadcast::new(&s_p, || status.clone().into());
+                e_p.borrow()
+                    .on_process_status(status)
+                    .map(move |_| broadcast.send())
+                    .boxed()
+            }
+            Message::Flush(tx) => {
+                let _ = tx.send(());
+                futures::future::ready(()).boxed()
+            }
+        }));
+        tokio::task::spawn_local(rx_r.for_each(move |message| match message {
+            Message::Event(status) => {
+                let broadcast = Broadcast::new(&s_r, || status.clone().into());
+                e_r.borrow()
+                    .on_runtime_status(status)
+                    .map(move |_| broadcast.send())
+                    .boxed()
+            }
+            Message::Flush(tx) => {
+                let _ = tx.send(());
+                futures::future::ready(()).boxed()
+            }
+        }));
 
         Self {
             tx_process: tx_p,
             tx_runtime: tx_r,
+            subscribers,
         }
     }
+
+    /// Subscribe to events accepted by `filter`, emitted by this emitter and its clones.
+    ///
+    /// Events are delivered once handled by the Supervisor. Up to `SUBSCRIBER_BUFFER` events
+    /// are buffered until received, later ones are dropped; drop the receiver to unsubscribe.
+    /// The emitter is available in `Context::emitter` once the runtime is started in `Server`
+    /// mode, i.e. not in `Runtime::deploy`, in the runtime factory or in `Command` mode
+    pub fn subscribe(&self, filter: EventFilter) -> mpsc::Receiver<EventKind> {
+        // each sender is guaranteed an additional slot
+        let (tx, rx) = mpsc::channel(Self::SUBSCRIBER_BUFFER - 1);
+        self.subscribers.lock().unwrap().push((filter, tx));
+        rx
+    }
+}
+
+/// Event delivered to subscribers once handled by the Supervisor
+struct Broadcast(Option<(Subscribers, EventKind)>);
+
+impl Broadcast {
+    fn new(subscribers: &Subscribers, event: impl FnOnce() -> EventKind) -> Self {
+        match subscribers.lock().unwrap().is_empty() {
+            true => Self(None),
+            false => Self(Some((subscribers.clone(), event()))),
+        }
+    }
+
+    /// Deliver the event to matching subscribers, dropping it for those not keeping up
+    fn send(self) {
+        let (subscribers, event) = match self.0 {
+            Some(broadcast) => broadcast,
+            None => return,
+        };
+        let mut subscribers = subscribers.lock().unwrap();
+        let mut retained = Vec::with_capacity(subscribers.len());
+        for (filter, mut tx) in subscribers.drain(..) {
+            let retain = match filter.matches(&event) {
+                true => !matches!(tx.try_send(event.clone()), Err(e) if e.is_disconnected()),
+                false => !tx.is_closed(),
+            };
+            if retain {
+                retained.push((filter, tx));
+            }
+        }
+        *subscribers = retained;
+    }
 }
 
 impl EventEmitter {
@@ -129,8 +286,29 @@ impl EventEmitter {
     /// Emit an event
     pub fn emit(&mut self, event: impl Into<EventKind>) -> BoxFuture<()> {
         match event.into() {
-            EventKind::Process(status) => self.tx_process.send(status).then(|_| async {}).boxed(),
-            EventKind::Runtime(status) => self.tx_runtime.send(status).then(|_| async {}).boxed(),
+            EventKind::Process(status) => self
+                .tx_process
+                .send(Message::Event(status))
+                .then(|_| async {})
+                .boxed(),
+            EventKind::Runtime(status) => self
+                .tx_runtime
+                .send(Message::Event(status))
+                .then(|_| async {})
+                .boxed(),
+        }
+    }
+
+    /// Wait until all events emitted so far have been handled
+    pub fn flush(&mut self) -> BoxFuture<()> {
+        let (tx_p, rx_p) = oneshot::channel();
+        let (tx_r, rx_r) = oneshot::channel();
+        let process = self.tx_process.send(Message::Flush(tx_p));
+        let runtime = self.tx_runtime.send(Message::Flush(tx_r));
+        async move {
+            let _ = futures::future::join(process, runtime).await;
+            let _ = futures::future::join(rx_p, rx_r).await;
         }
+        .boxed()
     }
 }
@@ -4,27 +4,56 @@ pub use ya_runtime_api::server::{
     RuntimeState, RuntimeStatus, RuntimeStatusKind,
 };
 
+pub use checkpoint::Checkpoint;
 pub use cli::Command;
+pub use commands::RuntimeCommands;
 pub use context::{Context, RunCommandContext, RunCommandExt};
-pub use error::{Error, ErrorExt};
-pub use event::{EventEmitter, EventKind};
-pub use runner::{build, run, run_with};
+pub use error::{Error, ErrorExt, ErrorKind};
+pub use event::{EventEmitter, EventFilter, EventKind, EventType};
+pub use limits::{LimitedChild, Limits};
+pub use output::{Capture, Encoding};
+pub use plugin::{PluginDescriptor, Preset};
+pub use runner::{build, exit_code, run, run_with};
 pub use runtime::*;
+pub use scheduler::{QueueDepth, QueueOrder, Scheduler};
+pub use self_test::{
+    Check, CheckOutcome, CheckReport, CheckResult, CheckStatus, SelfTest, SelfTestReport,
+};
+pub use state::{StateLock, StateStore};
+pub use volumes::{Volume, Volumes};
 
+mod checkpoint;
 pub mod cli;
+pub mod commands;
 mod common;
 mod context;
 pub mod env;
 pub mod error;
 mod event;
+mod limits;
+mod output;
+mod plugin;
+pub mod recording;
+mod registry;
 mod runner;
 mod runtime;
+mod scheduler;
+mod self_test;
 pub mod serialize;
 pub mod server;
+mod state;
+mod trace;
+mod volumes;
+
+#[cfg(feature = "debugger")]
+pub mod debugger;
 
 #[cfg(feature = "logger")]
 pub mod logger;
 
+#[cfg(feature = "sandbox")]
+pub mod sandbox;
+
 #[cfg(feature = "macros")]
 #[allow(unused_imports)]
 #[macro_use]
@@ -0,0 +1,483 @@
+use std::collections::HashSet;
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::process::{Child, Command};
+
+use crate::error::Error;
+use crate::runtime::{ProcessId, RETURN_CODE_CPU_LIMIT, RETURN_CODE_FILE_SIZE_LIMIT};
+
+/// Resource limits applied to processes spawned with `RunCommandContext::spawn`
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    /// CPU time limit
+    pub cpu_time: Option<Duration>,
+    /// Number of CPU cores available to the process (cgroup v2 only)
+    pub cpu_cores: Option<f64>,
+    /// Memory limit, in bytes
+    pub memory: Option<u64>,
+    /// Maximum size of a file created by the process, in bytes
+    pub file_size: Option<u64>,
+    /// Maximum number of open file descriptors
+    pub open_files: Option<u64>,
+    /// Parent cgroup of per-command cgroups. Defaults to the cgroup of the runtime,
+    /// which must not contain processes other than the runtime's
+    pub cgroup: Option<PathBuf>,
+}
+
+impl Limits {
+    fn is_empty(&self) -> bool {
+        self.cpu_time.is_none()
+            && self.cpu_cores.is_none()
+            && self.memory.is_none()
+            && self.file_size.is_none()
+            && self.open_files.is_none()
+    }
+}
+
+/// Processes spawned by a command, which have not been waited for yet
+#[derive(Clone, Default)]
+pub(crate) struct Children(Arc<Mutex<HashSet<u32>>>);
+
+impl Children {
+    fn insert(&self, id: Option<u32>) {
+        if let Some(id) = id {
+            self.0.lock().unwrap().insert(id);
+        }
+    }
+
+    fn remove(&self, id: Option<u32>) {
+        if let Some(id) = id {
+            self.0.lock().unwrap().remove(&id);
+        }
+    }
+
+    /// Kill all processes. Their `LimitedChild` handles must still be alive,
+    /// i.e. the processes cannot be reaped yet
+    pub(crate) fn kill(&self) {
+        for _id in self.0.lock().unwrap().drain() {
+            #[cfg(unix)]
+            unsafe {
+                libc::kill(_id as libc::pid_t, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Return code of a command, shared by clones of its `RunCommandContext`
+#[derive(Clone, Default)]
+pub(crate) struct ReturnCode(Arc<Mutex<Option<i32>>>);
+
+impl ReturnCode {
+    pub(crate) fn set(&self, code: i32) {
+        self.0.lock().unwrap().replace(code);
+    }
+
+    pub(crate) fn get(&self) -> Option<i32> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Child process, constrained by `Limits`
+pub struct LimitedChild {
+    child: Child,
+    id: Option<u32>,
+    children: Children,
+    return_code: ReturnCode,
+    /// Soft CPU time limit, enforced with SIGXCPU and then SIGKILL
+    cpu_time: Option<Duration>,
+    #[cfg(target_os = "linux")]
+    cgroup: Option<cgroup::Cgroup>,
+}
+
+impl LimitedChild {
+    /// Wait for the process to exit and return its exit code, which also becomes
+    /// the return code of the command. Limit violations are reported with
+    /// the `RETURN_CODE_*_LIMIT` codes
+    pub async fn wait(&mut self) -> Result<i32, Error> {
+        let code = self.exit_code().await?;
+        self.return_code.set(code);
+        Ok(code)
+    }
+
+    async fn exit_code(&mut self) -> Result<i32, Error> {
+        // CPU time is sampled until the process exits, since it cannot be read once reaped
+        let mut cpu_used = Duration::ZERO;
+        let sample = self.cpu_time.is_some() && cfg!(target_os = "linux");
+        let status = loop {
+            tokio::select! {
+                status = self.child.wait() => break status?,
+                _ = tokio::time::sleep(CPU_SAMPLE_INTERVAL), if sample => {
+                    if let Some(used) = self.id.and_then(cpu_time) {
+                        cpu_used = used;
+                    }
+                }
+            }
+        };
+        self.children.remove(self.id.take());
+
+        // the cgroup is removed once its process has been reaped
+        #[cfg(target_os = "linux")]
+        if self.cgroup.take().map(|c| c.oom_killed()).unwrap_or(false) {
+            return Ok(crate::runtime::RETURN_CODE_MEMORY_LIMIT);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            let cpu_exceeded = self.cpu_time.map(|limit| cpu_used >= limit);
+            match status.signal() {
+                Some(libc::SIGXCPU) => return Ok(RETURN_CODE_CPU_LIMIT),
+                // the hard limit is enforced with SIGKILL when SIGXCPU is handled or ignored
+                Some(libc::SIGKILL) if cpu_exceeded == Some(true) => {
+                    return Ok(RETURN_CODE_CPU_LIMIT)
+                }
+                Some(libc::SIGXFSZ) => return Ok(RETURN_CODE_FILE_SIZE_LIMIT),
+                Some(signal) => return Ok(128 + signal),
+                None => (),
+            }
+        }
+
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+impl Drop for LimitedChild {
+    fn drop(&mut self) {
+        self.children.remove(self.id.take());
+    }
+}
+
+impl Deref for LimitedChild {
+    type Target = Child;
+
+    fn deref(&self) -> &Self::Target {
+        &self.child
+    }
+}
+
+impl DerefMut for LimitedChild {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.child
+    }
+}
+
+/// Spawn `command`, applying resource limits via `setrlimit` and cgroup v2 (when available)
+pub(crate) fn spawn(
+    mut command: Command,
+    limits: &Limits,
+    pid: ProcessId,
+    children: &Children,
+    return_code: &ReturnCode,
+    #[cfg(feature = "sandbox")] sandbox: Option<&crate::sandbox::SandboxPolicy>,
+) -> Result<LimitedChild, Error> {
+    #[cfg(target_os = "linux")]
+    let cgroup = match cgroup::Cgroup::create(limits, pid) {
+        Ok(cgroup) => cgroup,
+        Err(_e) => {
+            #[cfg(feature = "logger")]
+            log::warn!("Unable to create a cgroup, applying rlimits only: {}", _e);
+            None
+        }
+    };
+    #[cfg(not(target_os = "linux"))]
+    let _ = pid;
+
+    #[cfg(unix)]
+    if !limits.is_empty() {
+        #[cfg(target_os = "linux")]
+        let cgroup_fd = cgroup.as_ref().map(|cgroup| cgroup.procs_fd());
+        #[cfg(not(target_os = "linux"))]
+        let cgroup_fd: Option<std::os::unix::io::RawFd> = None;
+
+        let rlimits = rlimits(limits);
+        let memory = limits.memory;
+        let pre_exec = move || {
+            for (resource, value) in rlimits.iter() {
+                set_rlimit(*resource, value.0, value.1)?;
+            }
+            // move the current process to the cgroup
+            let in_cgroup = cgroup_fd
+                .map(|fd| unsafe { libc::write(fd, b"0".as_ptr() as *const _, 1) } == 1)
+                .unwrap_or(false);
+            // otherwise, fall back to limiting the address space
+            if let Some(memory) = memory.filter(|_| !in_cgroup) {
+                set_rlimit(libc::RLIMIT_AS as Resource, memory, memory)?;
+            }
+            Ok(())
+        };
+        // SAFETY: `pre_exec` only performs async-signal-safe system calls
+        unsafe { command.pre_exec(pre_exec) };
+    }
+
+    // namespaces are entered after joining the cgroup
+    #[cfg(feature = "sandbox")]
+    if let Some(policy) = sandbox {
+        crate::sandbox::apply(&mut command, policy)?;
+    }
+
+    let child = command.spawn()?;
+    let id = child.id();
+    children.insert(id);
+    Ok(LimitedChild {
+        child,
+        id,
+        children: children.clone(),
+        return_code: return_code.clone(),
+        cpu_time: limits.cpu_time.map(|t| Duration::from_secs(cpu_secs(t))),
+        #[cfg(target_os = "linux")]
+        cgroup,
+    })
+}
+
+const CPU_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// CPU time limit in whole seconds, as set with `RLIMIT_CPU`
+fn cpu_secs(cpu_time: Duration) -> u64 {
+    cpu_time.as_secs().max(1)
+}
+
+/// CPU time consumed by a running process
+#[cfg(target_os = "linux")]
+fn cpu_time(id: u32) -> Option<Duration> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", id)).ok()?;
+    // fields following the parenthesized executable name, starting with the process state;
+    // `utime` and `stime` are expressed in clock ticks
+    let fields = stat
+        .rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    let ticks = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+    let hz = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    (hz > 0).then(|| Duration::from_secs_f64(ticks as f64 / hz as f64))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cpu_time(_id: u32) -> Option<Duration> {
+    None
+}
+
+#[cfg(unix)]
+type Resource = i32;
+
+#[cfg(unix)]
+fn rlimits(limits: &Limits) -> Vec<(Resource, (u64, u64))> {
+    let mut rlimits = Vec::new();
+    if let Some(cpu_time) = limits.cpu_time {
+        // SIGXCPU is sent on reaching the soft limit, SIGKILL on the hard one
+        let secs = cpu_secs(cpu_time);
+        rlimits.push((libc::RLIMIT_CPU as Resource, (secs, secs + 1)));
+    }
+    if let Some(file_size) = limits.file_size {
+        rlimits.push((libc::RLIMIT_FSIZE as Resource, (file_size, file_size)));
+    }
+    if let Some(open_files) = limits.open_files {
+        rlimits.push((libc::RLIMIT_NOFILE as Resource, (open_files, open_files)));
+    }
+    rlimits
+}
+
+#[cfg(unix)]
+fn set_rlimit(resource: Resource, soft: u64, hard: u64) -> std::io::Result<()> {
+    let rlimit = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    match unsafe { libc::setrlimit(resource as _, &rlimit) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod cgroup {
+    use std::fs::{File, OpenOptions};
+    use std::io;
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use super::Limits;
+    use crate::runtime::ProcessId;
+
+    const ROOT: &str = "/sys/fs/cgroup";
+    /// Leaf cgroup of the runtime processes, allowing controllers to be enabled for its parent
+    const LEAF: &str = "ya-runtime";
+    const CPU_PERIOD: u64 = 100_000;
+    const REMOVAL_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Per-command cgroup, removed on drop or, while still populated, once its processes exit
+    pub struct Cgroup {
+        path: PathBuf,
+        procs: File,
+    }
+
+    impl Cgroup {
+        /// Create a cgroup for limits enforced by cgroup controllers.
+        /// Returns `None` when no such limits are set
+        pub fn create(limits: &Limits, pid: ProcessId) -> io::Result<Option<Self>> {
+            if limits.memory.is_none() && limits.cpu_cores.is_none() {
+                return Ok(None);
+            }
+
+            let parent = match limits.cgroup {
+                Some(ref parent) => parent.clone(),
+                None => current()?,
+            };
+            enable_controllers(&parent, limits)?;
+            let path = parent.join(format!("ya-runtime-{}-{}", std::process::id(), pid));
+            std::fs::create_dir(&path)?;
+
+            let result = (|| {
+                if let Some(memory) = limits.memory {
+                    std::fs::write(path.join("memory.max"), memory.to_string())?;
+                    let _ = std::fs::write(path.join("memory.swap.max"), "0");
+                }
+                if let Some(cores) = limits.cpu_cores {
+                    let quota = ((cores * CPU_PERIOD as f64) as u64).max(1000);
+                    std::fs::write(path.join("cpu.max"), format!("{} {}", quota, CPU_PERIOD))?;
+                }
+                OpenOptions::new()
+                    .write(true)
+                    .open(path.join("cgroup.procs"))
+            })();
+
+            match result {
+                Ok(procs) => Ok(Some(Self { path, procs })),
+                Err(e) => {
+                    let _ = std::fs::remove_dir(&path);
+                    Err(e)
+                }
+            }
+        }
+
+        pub fn procs_fd(&self) -> RawFd {
+            self.procs.as_raw_fd()
+        }
+
+        /// Check whether any process was killed by the OOM killer
+        pub fn oom_killed(&self) -> bool {
+            let events = std::fs::read_to_string(self.path.join("memory.events"));
+            events
+                .ok()
+                .and_then(|events| {
+                    events
+                        .lines()
+                        .filter_map(|l| l.strip_prefix("oom_kill "))
+                        .find_map(|v| v.trim().parse::<u64>().ok())
+                })
+                .map(|count| count > 0)
+                .unwrap_or(false)
+        }
+    }
+
+    impl Drop for Cgroup {
+        fn drop(&mut self) {
+            let path = std::mem::take(&mut self.path);
+            match std::fs::remove_dir(&path) {
+                Err(e) if e.raw_os_error() == Some(libc::EBUSY) => remove_when_empty(path),
+                Err(_e) => {
+                    #[cfg(feature = "logger")]
+                    log::warn!("Unable to remove cgroup {}: {}", path.display(), _e);
+                }
+                Ok(_) => (),
+            }
+        }
+    }
+
+    /// Remove a cgroup in the background, once none of its processes are alive
+    fn remove_when_empty(path: PathBuf) {
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                #[cfg(feature = "logger")]
+                log::warn!("Unable to remove cgroup {}: still in use", path.display());
+                return;
+            }
+        };
+        handle.spawn(async move {
+            while populated(&path) {
+                tokio::time::sleep(REMOVAL_INTERVAL).await;
+            }
+            if let Err(_e) = std::fs::remove_dir(&path) {
+                #[cfg(feature = "logger")]
+                log::warn!("Unable to remove cgroup {}: {}", path.display(), _e);
+            }
+        });
+    }
+
+    /// Check whether any process of the cgroup (or its descendants) is alive
+    fn populated(path: &Path) -> bool {
+        std::fs::read_to_string(path.join("cgroup.events"))
+            .map(|events| events.lines().any(|l| l == "populated 1"))
+            .unwrap_or(false)
+    }
+
+    /// Enable controllers required by `limits` in child cgroups of `parent`.
+    /// Controllers cannot be enabled in a cgroup with member processes (the cgroup v2
+    /// "no internal processes" rule), thus the runtime process is moved to the `LEAF`
+    /// cgroup first. Other member processes are never moved
+    fn enable_controllers(parent: &Path, limits: &Limits) -> io::Result<()> {
+        let path = parent.join("cgroup.subtree_control");
+        let enabled = std::fs::read_to_string(&path)?;
+        let control = [
+            ("memory", limits.memory.is_some()),
+            ("cpu", limits.cpu_cores.is_some()),
+        ]
+        .iter()
+        .filter(|(name, required)| *required && !enabled.split_whitespace().any(|c| c == *name))
+        .map(|(name, _)| format!("+{}", name))
+        .collect::<Vec<_>>()
+        .join(" ");
+        if control.is_empty() {
+            return Ok(());
+        }
+
+        match std::fs::write(&path, &control) {
+            Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+                move_runtime(parent, &parent.join(LEAF))?;
+                std::fs::write(&path, &control)
+            }
+            result => result,
+        }
+    }
+
+    /// Move the runtime process from `from` to `to`, provided that it is the only member
+    /// of `from`. Processes not owned by the runtime require a delegated `Limits::cgroup`
+    fn move_runtime(from: &Path, to: &Path) -> io::Result<()> {
+        let pid = std::process::id().to_string();
+        let procs = std::fs::read_to_string(from.join("cgroup.procs"))?;
+        if procs.lines().any(|p| p != pid) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "cgroup {} has other member processes, set a delegated cgroup in limits",
+                    from.display()
+                ),
+            ));
+        }
+
+        match std::fs::create_dir(to) {
+            Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+            _ => (),
+        }
+        std::fs::write(to.join("cgroup.procs"), pid)
+    }
+
+    /// Resolve the cgroup v2 directory of the current process,
+    /// i.e. the parent of `LEAF` once the process has been moved there
+    fn current() -> io::Result<PathBuf> {
+        let contents = std::fs::read_to_string("/proc/self/cgroup")?;
+        let relative = contents
+            .lines()
+            .find_map(|l| l.strip_prefix("0::"))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "cgroup v2 not available"))?;
+        let path = Path::new(ROOT).join(relative.trim_start_matches('/'));
+        match (path.file_name(), path.parent()) {
+            (Some(name), Some(parent)) if name == LEAF => Ok(parent.to_path_buf()),
+            _ => Ok(path),
+        }
+    }
+}
@@ -1,25 +1,286 @@
 use chrono::{DateTime, Local};
-use flexi_logger::{DeferredNow, Record};
-use std::env::current_dir;
+use flexi_logger::writers::LogWriter;
+use flexi_logger::{Age, Cleanup, Criterion, DeferredNow, LoggerHandle, Naming, Record};
+use futures::channel::mpsc;
+use futures::{FutureExt, StreamExt};
+use log::LevelFilter;
+use std::cell::RefCell;
+use std::fs::File;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::time::SystemTime;
 
+use crate::event::EventEmitter;
+use crate::runtime::ProcessId;
+
 const DEFAULT_LOG_LEVEL: &str = "info";
 const DEFAULT_FILE_LOG_LEVEL: &str = "debug";
-const DEFAULT_LOG_DIR: &str = "logs";
 const DEFAULT_LOG_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%z";
+const DEFAULT_ROTATE_SIZE: u64 = 10 * 1024 * 1024;
+const DEFAULT_KEEP_FILES: usize = 5;
+
+const ENV_LOG_DIR: &str = "YA_RUNTIME_LOG_DIR";
+const ENV_ROTATE_SIZE: &str = "YA_RUNTIME_LOG_ROTATE_SIZE";
+const ENV_ROTATE_AGE: &str = "YA_RUNTIME_LOG_ROTATE_AGE";
+const ENV_KEEP_FILES: &str = "YA_RUNTIME_LOG_KEEP_FILES";
+const ENV_KEEP_COMPRESSED: &str = "YA_RUNTIME_LOG_KEEP_COMPRESSED";
+
+/// File logger configuration
+#[derive(Clone, Debug)]
+pub struct LoggerConfig {
+    /// Directory to store log files at
+    pub directory: PathBuf,
+    /// Rotate the log file when it exceeds the given size (in bytes)
+    pub rotate_size: Option<u64>,
+    /// Rotate the log file when the given period of time has passed
+    pub rotate_age: Option<Age>,
+    /// Number of rotated, uncompressed log files to keep
+    pub keep_files: usize,
+    /// Number of rotated log files to keep in a compressed form.
+    /// Compression is disabled when set to 0
+    pub keep_compressed_files: usize,
+}
+
+impl LoggerConfig {
+    /// Create a new configuration with default rotation and retention settings
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            rotate_size: Some(DEFAULT_ROTATE_SIZE),
+            rotate_age: None,
+            keep_files: DEFAULT_KEEP_FILES,
+            keep_compressed_files: 0,
+        }
+    }
+
+    /// Create a new configuration and override its settings
+    /// with values of the following environment variables:
+    ///
+    /// - `YA_RUNTIME_LOG_DIR` - log directory
+    /// - `YA_RUNTIME_LOG_ROTATE_SIZE` - rotation size, e.g. `512K`, `10M`, `1G`; `0` disables
+    /// - `YA_RUNTIME_LOG_ROTATE_AGE` - rotation age: `day`, `hour`, `minute` or `second`
+    /// - `YA_RUNTIME_LOG_KEEP_FILES` - number of uncompressed log files to keep
+    /// - `YA_RUNTIME_LOG_KEEP_COMPRESSED` - number of compressed log files to keep
+    pub fn from_env_vars(directory: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        Self::from_vars(directory, |name| std::env::var(name).ok())
+    }
+
+    /// Create a new configuration and override its settings with values of variables
+    /// listed in `from_env_vars`, as returned by `vars`
+    pub fn from_vars(
+        directory: impl Into<PathBuf>,
+        vars: impl Fn(&str) -> Option<String>,
+    ) -> anyhow::Result<Self> {
+        let env_var = |name: &str| {
+            vars(name)
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let mut config = Self::new(directory);
+
+        if let Some(dir) = env_var(ENV_LOG_DIR) {
+            config.directory = PathBuf::from(dir);
+        }
+        if let Some(size) = env_var(ENV_ROTATE_SIZE) {
+            config.rotate_size = match parse_size(&size)? {
+                0 => None,
+                size => Some(size),
+            };
+        }
+        if let Some(age) = env_var(ENV_ROTATE_AGE) {
+            config.rotate_age = Some(parse_age(&age)?);
+        }
+        if let Some(keep) = env_var(ENV_KEEP_FILES) {
+            config.keep_files = parse_count(ENV_KEEP_FILES, &keep)?;
+        }
+        if let Some(keep) = env_var(ENV_KEEP_COMPRESSED) {
+            config.keep_compressed_files = parse_count(ENV_KEEP_COMPRESSED, &keep)?;
+        }
+
+        Ok(config)
+    }
+
+    fn criterion(&self) -> Option<Criterion> {
+        match (self.rotate_age, self.rotate_size) {
+            (Some(age), Some(size)) => Some(Criterion::AgeOrSize(age, size)),
+            (Some(age), None) => Some(Criterion::Age(age)),
+            (None, Some(size)) => Some(Criterion::Size(size)),
+            (None, None) => None,
+        }
+    }
 
-pub fn start_file_logger() -> anyhow::Result<flexi_logger::LoggerHandle> {
-    let log_dir = current_dir()?.join(DEFAULT_LOG_DIR);
-    std::fs::create_dir_all(&log_dir)?;
+    fn cleanup(&self) -> Cleanup {
+        match (self.keep_files, self.keep_compressed_files) {
+            (keep, 0) => Cleanup::KeepLogFiles(keep),
+            (0, compressed) => Cleanup::KeepCompressedFiles(compressed),
+            (keep, compressed) => Cleanup::KeepLogAndCompressedFiles(keep, compressed),
+        }
+    }
+}
+
+/// Keeps the started loggers alive
+#[derive(Default)]
+pub struct LoggerGuard {
+    _logger: Option<LoggerHandle>,
+    #[cfg(feature = "tracing")]
+    _tracing: Option<flexi_logger::writers::FileLogWriterHandle>,
+}
+
+/// Start the file logger, falling back to a stderr logger on error.
+/// With the `tracing` feature enabled, a `tracing` subscriber is installed as well.
+pub fn start(log_dir: anyhow::Result<PathBuf>) -> LoggerGuard {
+    let mut guard = LoggerGuard::default();
+    let config = match log_dir.and_then(LoggerConfig::from_env_vars) {
+        Ok(config) => Some(config),
+        Err(error) => {
+            guard._logger = fallback(error);
+            None
+        }
+    };
 
-    Ok(build_logger(Some(DEFAULT_FILE_LOG_LEVEL))?
-        .log_to_file(flexi_logger::FileSpec::default().directory(log_dir))
+    if let Some(ref config) = config {
+        guard._logger = match start_file_logger(config) {
+            Ok(handle) => Some(handle),
+            Err(error) => fallback(error),
+        };
+    }
+
+    #[cfg(feature = "tracing")]
+    match start_tracing(config.as_ref()) {
+        Ok(handle) => guard._tracing = handle,
+        Err(error) => log::warn!("Unable to start tracing: {:?}", error),
+    }
+
+    guard
+}
+
+fn fallback(error: anyhow::Error) -> Option<LoggerHandle> {
+    let handle = start_logger().expect("Failed to start logging");
+    log::warn!("Using fallback logging due to an error: {:?}", error);
+    Some(handle)
+}
+
+pub fn start_file_logger(config: &LoggerConfig) -> anyhow::Result<LoggerHandle> {
+    std::fs::create_dir_all(&config.directory)?;
+
+    let mut logger = build_logger(Some(DEFAULT_FILE_LOG_LEVEL))?
+        .log_to_file_and_writer(
+            flexi_logger::FileSpec::default().directory(&config.directory),
+            Box::new(CommandLogWriter),
+        )
+        .duplicate_to_stderr(log_tty_dup_level()?);
+
+    if let Some(criterion) = config.criterion() {
+        logger = logger.rotate(criterion, Naming::Timestamps, config.cleanup());
+    }
+
+    Ok(logger.start()?)
+}
+
+pub fn start_logger() -> anyhow::Result<LoggerHandle> {
+    Ok(build_logger(Option::<String>::None)?
+        .log_to_writer(Box::new(CommandLogWriter))
         .duplicate_to_stderr(log_tty_dup_level()?)
         .start()?)
 }
 
-pub fn start_logger() -> anyhow::Result<flexi_logger::LoggerHandle> {
-    Ok(build_logger(Option::<String>::None)?.start()?)
+/// Install a `tracing` subscriber, writing to a file in the log directory (if provided).
+/// Events are also forwarded to the `log` facade, which prints them to stderr
+/// and routes them to the command they were emitted by
+#[cfg(feature = "tracing")]
+pub fn start_tracing(
+    config: Option<&LoggerConfig>,
+) -> anyhow::Result<Option<flexi_logger::writers::FileLogWriterHandle>> {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+    let (file_layer, handle) = match config {
+        Some(config) => {
+            std::fs::create_dir_all(&config.directory)?;
+
+            let spec = flexi_logger::FileSpec::default()
+                .directory(&config.directory)
+                .discriminant("trace");
+            let mut builder = flexi_logger::writers::FileLogWriter::builder(spec);
+            if let Some(criterion) = config.criterion() {
+                builder = builder.rotate(criterion, Naming::Timestamps, config.cleanup());
+            }
+            let (writer, handle) = builder.try_build_with_handle()?;
+
+            let file_layer = fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .with_filter(EnvFilter::new(DEFAULT_FILE_LOG_LEVEL));
+            (Some(file_layer), Some(handle))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(file_layer)
+        .with(LogLayer)
+        .try_init()?;
+
+    Ok(handle)
+}
+
+/// Forwards `tracing` events to the `log` facade
+#[cfg(feature = "tracing")]
+struct LogLayer;
+
+#[cfg(feature = "tracing")]
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for LogLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+        let meta = event.metadata();
+        let level = match *meta.level() {
+            tracing::Level::ERROR => log::Level::Error,
+            tracing::Level::WARN => log::Level::Warn,
+            tracing::Level::INFO => log::Level::Info,
+            tracing::Level::DEBUG => log::Level::Debug,
+            tracing::Level::TRACE => log::Level::Trace,
+        };
+        let metadata = log::Metadata::builder()
+            .level(level)
+            .target(meta.target())
+            .build();
+        let logger = log::logger();
+        if !logger.enabled(&metadata) {
+            return;
+        }
+
+        let mut message = EventMessage::default();
+        event.record(&mut message);
+        logger.log(
+            &log::Record::builder()
+                .metadata(metadata)
+                .module_path(meta.module_path())
+                .file(meta.file())
+                .line(meta.line())
+                .args(format_args!("{}", message.0))
+                .build(),
+        );
+    }
+}
+
+/// Event message followed by the remaining fields, formatted as `key=value`
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct EventMessage(String);
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for EventMessage {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        use std::fmt::Write;
+
+        let separator = if self.0.is_empty() { "" } else { " " };
+        let _ = match field.name() {
+            "message" => write!(self.0, "{}{:?}", separator, value),
+            name => write!(self.0, "{}{}={:?}", separator, name, value),
+        };
+    }
 }
 
 fn build_logger<S: ToString>(log_level: Option<S>) -> anyhow::Result<flexi_logger::Logger> {
@@ -55,10 +316,176 @@ fn log_tty_dup_level() -> anyhow::Result<flexi_logger::Duplicate> {
     })
 }
 
+tokio::task_local! {
+    static COMMAND_LOG: CommandLog;
+}
+
+/// Log record routing for a single command
+#[derive(Clone)]
+pub(crate) struct CommandLog {
+    inner: Rc<RefCell<CommandLogInner>>,
+}
+
+struct CommandLogInner {
+    id: ProcessId,
+    file: Option<File>,
+    stderr: Option<(LevelFilter, EventEmitter)>,
+    lines: Option<mpsc::UnboundedSender<Vec<u8>>>,
+}
+
+impl CommandLog {
+    pub(crate) fn new(id: ProcessId) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(CommandLogInner {
+                id,
+                file: None,
+                stderr: None,
+                lines: None,
+            })),
+        }
+    }
+
+    /// Tee log records into a file
+    pub(crate) fn set_file(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        self.inner.borrow_mut().file.replace(file);
+        Ok(())
+    }
+
+    /// Forward log records to the requestor as stderr events
+    pub(crate) fn set_stderr(&self, level: LevelFilter, emitter: EventEmitter) {
+        self.inner.borrow_mut().stderr.replace((level, emitter));
+    }
+
+    /// Execute the future with all log records routed to this command.
+    /// Records forwarded as stderr events are emitted alongside the future
+    /// and flushed before it resolves
+    pub(crate) fn scope<F: Future>(self, fut: F) -> impl Future<Output = F::Output> {
+        let (tx, rx) = mpsc::unbounded();
+        self.inner.borrow_mut().lines.replace(tx);
+
+        let log = self.clone();
+        let forward = self.clone().forward(rx);
+        let fut = async move {
+            let output = COMMAND_LOG.scope(self, fut).await;
+            log.inner.borrow_mut().lines.take();
+            output
+        };
+        futures::future::join(fut, forward).map(|(output, _)| output)
+    }
+
+    async fn forward(self, mut rx: mpsc::UnboundedReceiver<Vec<u8>>) {
+        while let Some(line) = rx.next().await {
+            let (id, emitter) = {
+                let inner = self.inner.borrow();
+                (inner.id, inner.stderr.as_ref().map(|(_, e)| e.clone()))
+            };
+            if let Some(mut emitter) = emitter {
+                emitter.command_stderr(id, line).await;
+            }
+        }
+    }
+
+    fn write(&self, now: &mut DeferredNow, record: &Record) -> std::io::Result<()> {
+        let mut inner = match self.inner.try_borrow_mut() {
+            Ok(inner) => inner,
+            Err(_) => return Ok(()),
+        };
+        let forward = match inner.stderr {
+            Some((level, _)) => record.level() <= level,
+            None => false,
+        };
+        if inner.file.is_none() && !forward {
+            return Ok(());
+        }
+
+        let id = inner.id;
+        let mut line = Vec::new();
+        format_record(&mut line, now, record, Some(id))?;
+        line.push(b'\n');
+
+        if let Some(file) = inner.file.as_mut() {
+            file.write_all(&line)?;
+        }
+        if let Some(lines) = inner.lines.as_ref().filter(|_| forward) {
+            let _ = lines.unbounded_send(line);
+        }
+
+        Ok(())
+    }
+}
+
+/// Return the ID of the command currently being executed
+pub(crate) fn current_command() -> Option<ProcessId> {
+    COMMAND_LOG
+        .try_with(|log| log.inner.try_borrow().ok().map(|inner| inner.id))
+        .ok()
+        .flatten()
+}
+
+struct CommandLogWriter;
+
+impl LogWriter for CommandLogWriter {
+    fn write(&self, now: &mut DeferredNow, record: &Record) -> std::io::Result<()> {
+        COMMAND_LOG
+            .try_with(|log| log.write(now, record))
+            .unwrap_or(Ok(()))
+    }
+
+    fn flush(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn parse_size(value: &str) -> anyhow::Result<u64> {
+    let value = value.to_uppercase();
+    let value = value.trim_end_matches('B');
+    let (digits, multiplier) = match value.chars().last() {
+        Some('K') => (&value[..value.len() - 1], 1024),
+        Some('M') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('G') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    let invalid = || anyhow::anyhow!("Invalid {} value: {}", ENV_ROTATE_SIZE, value);
+    let size: u64 = digits.trim().parse().map_err(|_| invalid())?;
+    size.checked_mul(multiplier).ok_or_else(invalid)
+}
+
+fn parse_age(value: &str) -> anyhow::Result<Age> {
+    Ok(match value.to_lowercase().as_str() {
+        "day" => Age::Day,
+        "hour" => Age::Hour,
+        "minute" => Age::Minute,
+        "second" => Age::Second,
+        _ => anyhow::bail!("Invalid {} value: {}", ENV_ROTATE_AGE, value),
+    })
+}
+
+fn parse_count(name: &str, value: &str) -> anyhow::Result<usize> {
+    value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid {} value: {}", name, value))
+}
+
 fn log_format(
     w: &mut dyn std::io::Write,
     now: &mut DeferredNow,
     record: &Record,
+) -> Result<(), std::io::Error> {
+    format_record(w, now, record, current_command())
+}
+
+fn format_record(
+    w: &mut dyn std::io::Write,
+    now: &mut DeferredNow,
+    record: &Record,
+    command: Option<ProcessId>,
 ) -> Result<(), std::io::Error> {
     //use DateTime::<Local> instead of DateTime::<UTC> to obtain local date
     let now = SystemTime::from(*now.now());
@@ -66,12 +493,23 @@ fn log_format(
     //format date as following: 2020-08-27T07:56:22.348+02:00 (local date + time zone with milliseconds precision)
     let date_format = local_date.format(DEFAULT_LOG_FORMAT);
 
-    write!(
-        w,
-        "[{} {:5} {}] {}",
-        date_format,
-        record.level(),
-        record.module_path().unwrap_or("<unnamed>"),
-        record.args()
-    )
+    match command {
+        Some(id) => write!(
+            w,
+            "[{} {:5} {} pid={}] {}",
+            date_format,
+            record.level(),
+            record.module_path().unwrap_or("<unnamed>"),
+            id,
+            record.args()
+        ),
+        None => write!(
+            w,
+            "[{} {:5} {}] {}",
+            date_format,
+            record.level(),
+            record.module_path().unwrap_or("<unnamed>"),
+            record.args()
+        ),
+    }
 }
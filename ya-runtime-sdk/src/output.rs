@@ -0,0 +1,267 @@
+//! Command output capture and encoding
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use ya_runtime_api::server::proto::output::Type;
+use ya_runtime_api::server::proto::Output;
+
+/// Command output capture mode, requested in `RunProcess`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Capture {
+    /// Drop the output
+    Discard,
+    /// Emit up to the specified number of trailing bytes when the stream ends
+    AtEnd(u64),
+    /// Emit output incrementally, in chunks of up to the specified number of bytes
+    Stream(u64),
+}
+
+impl Capture {
+    /// Capture mode of streams without a `RunProcess` output specification
+    pub const DEFAULT: Capture = Capture::Stream(16384);
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl From<&Output> for Capture {
+    fn from(output: &Output) -> Self {
+        match output.r#type {
+            Some(Type::Discard(_)) => Self::Discard,
+            Some(Type::AtEnd(size)) => Self::AtEnd(size),
+            Some(Type::Stream(size)) => Self::Stream(size),
+            None => Self::default(),
+        }
+    }
+}
+
+impl From<Capture> for Output {
+    fn from(capture: Capture) -> Self {
+        Output {
+            r#type: Some(match capture {
+                Capture::Discard => Type::Discard(true),
+                Capture::AtEnd(size) => Type::AtEnd(size),
+                Capture::Stream(size) => Type::Stream(size),
+            }),
+        }
+    }
+}
+
+/// Command output stream
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Splits output into chunks according to a `Capture` mode.
+///
+/// Streamed output is emitted as soon as it is read, split after newlines where possible,
+/// so that partial lines (e.g. progress indicators or prompts) are not held back.
+pub(crate) struct Chunker {
+    capture: Capture,
+    buf: Vec<u8>,
+}
+
+impl Chunker {
+    pub fn new(capture: Capture) -> Self {
+        Self {
+            capture,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Consume `data` and return chunks ready to be emitted
+    pub fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        match self.capture {
+            Capture::Discard => (),
+            Capture::AtEnd(size) => {
+                self.buf.extend_from_slice(data);
+                let size = size as usize;
+                if self.buf.len() > size {
+                    self.buf.drain(..self.buf.len() - size);
+                }
+            }
+            Capture::Stream(size) => {
+                self.buf.extend_from_slice(data);
+                let size = (size as usize).max(1);
+                loop {
+                    let limit = self.buf.len().min(size);
+                    let end = match self.buf[..limit].iter().rposition(|b| *b == b'\n') {
+                        Some(pos) => pos + 1,
+                        None if self.buf.len() >= size => size,
+                        None if !self.buf.is_empty() => self.buf.len(),
+                        None => break,
+                    };
+                    chunks.push(self.buf.drain(..end).collect());
+                }
+            }
+        }
+        chunks
+    }
+
+    /// Return the remaining output
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        match self.buf.is_empty() {
+            true => None,
+            false => Some(std::mem::take(&mut self.buf)),
+        }
+    }
+}
+
+/// Command output encoding
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// Emit output as is
+    #[default]
+    Raw,
+    /// Emit valid UTF-8 text, never splitting multi-byte characters across events.
+    /// Invalid sequences are replaced with `U+FFFD`
+    Utf8,
+    /// Emit complete lines of UTF-8 text
+    Lines,
+    /// Emit base64-encoded output. Each event, except the last one, encodes a multiple of 3 bytes,
+    /// thus can be decoded on its own and the events concatenate into a valid base64 string
+    Base64,
+}
+
+/// Encodes output, holding back bytes which cannot be emitted yet
+#[derive(Default)]
+struct Encoder {
+    encoding: Encoding,
+    pending: Vec<u8>,
+}
+
+impl Encoder {
+    fn encode(&mut self, data: Vec<u8>) -> Option<Vec<u8>> {
+        if self.encoding == Encoding::Raw && self.pending.is_empty() {
+            return Some(data);
+        }
+        self.pending.extend(data);
+
+        let end = match self.encoding {
+            Encoding::Raw => self.pending.len(),
+            Encoding::Utf8 => utf8_boundary(&self.pending),
+            Encoding::Lines => match self.pending.iter().rposition(|b| *b == b'\n') {
+                Some(pos) => pos + 1,
+                None => 0,
+            },
+            Encoding::Base64 => self.pending.len() / 3 * 3,
+        };
+        match end {
+            0 => None,
+            _ => {
+                let output = self.pending.drain(..end).collect::<Vec<_>>();
+                Some(self.convert(output))
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Option<Vec<u8>> {
+        match self.pending.is_empty() {
+            true => None,
+            false => {
+                let output = std::mem::take(&mut self.pending);
+                Some(self.convert(output))
+            }
+        }
+    }
+
+    fn convert(&self, output: Vec<u8>) -> Vec<u8> {
+        match self.encoding {
+            Encoding::Raw => output,
+            Encoding::Utf8 | Encoding::Lines => match String::from_utf8(output) {
+                Ok(string) => string.into_bytes(),
+                Err(e) => String::from_utf8_lossy(e.as_bytes())
+                    .into_owned()
+                    .into_bytes(),
+            },
+            Encoding::Base64 => base64::encode(output).into_bytes(),
+        }
+    }
+}
+
+/// Return the length of the longest prefix of `bytes` which does not end
+/// with an incomplete UTF-8 sequence
+fn utf8_boundary(bytes: &[u8]) -> usize {
+    let mut offset = 0;
+    loop {
+        match std::str::from_utf8(&bytes[offset..]) {
+            Ok(_) => return bytes.len(),
+            Err(e) => match e.error_len() {
+                Some(len) => offset += e.valid_up_to() + len,
+                None => return offset + e.valid_up_to(),
+            },
+        }
+    }
+}
+
+/// Output encoders of a command, shared by clones of its `RunCommandContext`
+#[derive(Clone, Default)]
+pub(crate) struct Encoders {
+    inner: Rc<RefCell<(Encoder, Encoder)>>,
+}
+
+impl Encoders {
+    /// Set the encoding of a stream. Pending output is encoded with the new encoding
+    pub fn set(&self, stream: Stream, encoding: Encoding) {
+        self.with(stream, |e| e.encoding = encoding)
+    }
+
+    /// Encode `data`, returning output ready to be emitted
+    pub fn encode(&self, stream: Stream, data: Vec<u8>) -> Option<Vec<u8>> {
+        self.with(stream, |e| e.encode(data))
+    }
+
+    /// Return the remaining encoded output
+    pub fn finish(&self, stream: Stream) -> Option<Vec<u8>> {
+        self.with(stream, |e| e.finish())
+    }
+
+    fn with<T>(&self, stream: Stream, f: impl FnOnce(&mut Encoder) -> T) -> T {
+        let mut inner = self.inner.borrow_mut();
+        match stream {
+            Stream::Stdout => f(&mut inner.0),
+            Stream::Stderr => f(&mut inner.1),
+        }
+    }
+}
+
+/// Writes to the runtime's standard output.
+/// JSON values are written in a single write, terminated with a newline
+#[derive(Clone)]
+pub(crate) struct OutputWriter {
+    sink: Rc<RefCell<Box<dyn Write>>>,
+    pretty: bool,
+}
+
+impl OutputWriter {
+    pub fn new(sink: Box<dyn Write>, pretty: bool) -> Self {
+        Self {
+            sink: Rc::new(RefCell::new(sink)),
+            pretty,
+        }
+    }
+
+    /// Write a JSON value: a command result or an `Error`
+    pub fn json<T: Serialize + ?Sized>(&self, value: &T) -> io::Result<()> {
+        let mut bytes = match self.pretty {
+            true => serde_json::to_vec_pretty(value)?,
+            false => serde_json::to_vec(value)?,
+        };
+        bytes.push(b'\n');
+        self.raw(&bytes)
+    }
+
+    fn raw(&self, bytes: &[u8]) -> io::Result<()> {
+        let mut sink = self.sink.borrow_mut();
+        sink.write_all(bytes)?;
+        sink.flush()
+    }
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Runtime descriptor, read by the Provider Agent from the plugins directory
+/// (`ya-runtime-<name>.json`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PluginDescriptor {
+    pub name: String,
+    pub version: String,
+    pub supervisor_path: PathBuf,
+    pub runtime_path: PathBuf,
+    pub description: String,
+    pub extra_args: Vec<String>,
+}
+
+impl PluginDescriptor {
+    /// Default ExeUnit Supervisor path, relative to the plugins directory
+    pub const SUPERVISOR_PATH: &'static str = "exe-unit";
+
+    pub fn new(name: impl ToString, version: impl ToString, runtime_path: PathBuf) -> Self {
+        let name = name.to_string();
+        Self {
+            description: format!("{} runtime", name),
+            name,
+            version: version.to_string(),
+            supervisor_path: PathBuf::from(Self::SUPERVISOR_PATH),
+            runtime_path,
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+/// Provider Agent preset entry (`presets.json`), with zeroed usage coefficients
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Preset {
+    pub name: String,
+    pub exeunit_name: String,
+    pub pricing_model: String,
+    pub initial_price: f64,
+    pub usage_coeffs: BTreeMap<String, f64>,
+}
+
+impl Preset {
+    /// Counters reported by the ExeUnit Supervisor for every runtime
+    pub const SUPERVISOR_COUNTERS: [&'static str; 2] =
+        ["golem.usage.duration_sec", "golem.usage.cpu_sec"];
+
+    pub fn new<'c>(name: impl ToString, counters: impl IntoIterator<Item = &'c str>) -> Self {
+        let name = name.to_string();
+        let usage_coeffs = Self::SUPERVISOR_COUNTERS
+            .iter()
+            .copied()
+            .chain(counters)
+            .map(|counter| (counter.to_string(), 0.))
+            .collect();
+        Self {
+            exeunit_name: name.clone(),
+            name,
+            pricing_model: "linear".to_string(),
+            initial_price: 0.,
+            usage_coeffs,
+        }
+    }
+}
@@ -0,0 +1,633 @@
+//! Recording and replay of Runtime API sessions
+use futures::channel::mpsc;
+use futures::future::{BoxFuture, LocalBoxFuture};
+use futures::{FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ya_runtime_api::server::proto::response::create_network::Endpoint;
+use ya_runtime_api::server::proto::Output;
+use ya_runtime_api::server::*;
+
+use crate::common::{bytes, timestamp};
+use crate::error::Error;
+use crate::event::EventKind;
+pub use crate::output::Capture;
+use crate::runtime::{ProcessId, Runtime};
+use crate::server::Server;
+use crate::Context;
+
+/// Recorded session entry, stored as a single line of JSON
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordEntry {
+    /// Milliseconds since the UNIX epoch
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Message {
+    /// Request received from the supervisor
+    Request(Request),
+    /// Response sent to the supervisor
+    Response(Response),
+    /// Event sent to the supervisor
+    Event(Event),
+}
+
+/// Runtime API request
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Request {
+    Hello {
+        version: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    RunProcess {
+        bin: String,
+        args: Vec<String>,
+        work_dir: String,
+        stdout: Option<Capture>,
+        stderr: Option<Capture>,
+    },
+    KillProcess {
+        pid: ProcessId,
+        signal: i32,
+    },
+    CreateNetwork {
+        networks: Vec<NetworkDef>,
+        hosts: BTreeMap<String, String>,
+        interface: i32,
+    },
+    Shutdown,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkDef {
+    pub addr: String,
+    pub gateway: String,
+    pub mask: String,
+    pub if_addr: String,
+}
+
+/// Runtime API response
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Response {
+    Hello {
+        version: String,
+    },
+    RunProcess {
+        pid: ProcessId,
+    },
+    KillProcess,
+    CreateNetwork {
+        endpoint: Option<String>,
+    },
+    Shutdown,
+    Error {
+        code: i32,
+        message: String,
+        context: BTreeMap<String, String>,
+    },
+}
+
+/// Runtime API event
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Event {
+    #[serde(rename_all = "camelCase")]
+    ProcessStatus {
+        pid: ProcessId,
+        running: bool,
+        return_code: i32,
+        #[serde(with = "bytes")]
+        stdout: Vec<u8>,
+        #[serde(with = "bytes")]
+        stderr: Vec<u8>,
+    },
+    State {
+        name: String,
+        #[serde(with = "bytes")]
+        value: Vec<u8>,
+    },
+    Counter {
+        name: String,
+        value: f64,
+    },
+    Empty,
+}
+
+impl Event {
+    /// Check whether this event reports a stopped command
+    pub fn is_stopped(&self) -> bool {
+        matches!(self, Self::ProcessStatus { running: false, .. })
+    }
+
+    fn pid(&self) -> Option<ProcessId> {
+        match self {
+            Self::ProcessStatus { pid, .. } => Some(*pid),
+            _ => None,
+        }
+    }
+}
+
+/// Writes Runtime API requests, responses and events to a JSON-lines file
+#[derive(Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<LineWriter<File>>>,
+}
+
+impl Recorder {
+    /// Name of the recording file, relative to the working directory
+    pub const FILE_NAME: &'static str = "runtime-session.jsonl";
+
+    /// Open a recording file in append mode
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())
+            .map_err(|e| Error::from(e).with_context("path", path.as_ref().display()))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(LineWriter::new(file))),
+        })
+    }
+
+    /// Append an entry to the recording
+    pub fn record(&self, message: Message) {
+        let entry = RecordEntry {
+            timestamp: timestamp(),
+            message,
+        };
+        let result = serde_json::to_string(&entry)
+            .map_err(std::io::Error::from)
+            .and_then(|line| match self.file.lock() {
+                Ok(mut file) => writeln!(file, "{}", line),
+                Err(_) => Ok(()),
+            });
+        if let Err(_e) = result {
+            #[cfg(feature = "logger")]
+            log::warn!("Unable to record a Runtime API message: {}", _e);
+        }
+    }
+
+    /// Record a request and the response to it. The request is recorded before calling `f`,
+    /// thus preceding any events emitted by the handler
+    pub(crate) fn call<'a, T: 'a, F>(
+        recorder: Option<Self>,
+        request: impl FnOnce() -> Request,
+        f: impl FnOnce() -> F,
+        response: impl FnOnce(&T) -> Response + 'a,
+    ) -> LocalBoxFuture<'a, Result<T, ErrorResponse>>
+    where
+        F: Future<Output = Result<T, ErrorResponse>> + 'a,
+    {
+        let recorder = match recorder {
+            Some(recorder) => recorder,
+            None => return f().boxed_local(),
+        };
+        recorder.record(Message::Request(request()));
+        let fut = f();
+        async move {
+            let result = fut.await;
+            recorder.record(Message::Response(match result {
+                Ok(ref value) => response(value),
+                Err(ref err) => Response::from(err),
+            }));
+            result
+        }
+        .boxed_local()
+    }
+
+    /// Wrap an event handler, recording forwarded events
+    pub(crate) fn handler<H: RuntimeHandler>(&self, handler: H) -> RecordingHandler<H> {
+        RecordingHandler {
+            recorder: self.clone(),
+            handler,
+        }
+    }
+}
+
+pub(crate) struct RecordingHandler<H> {
+    recorder: Recorder,
+    handler: H,
+}
+
+impl<H: RuntimeHandler> RuntimeHandler for RecordingHandler<H> {
+    fn on_process_status<'a>(&self, status: ProcessStatus) -> BoxFuture<'a, ()> {
+        self.recorder
+            .record(Message::Event(EventKind::from(status.clone()).into()));
+        self.handler.on_process_status(status)
+    }
+
+    fn on_runtime_status<'a>(&self, status: RuntimeStatus) -> BoxFuture<'a, ()> {
+        self.recorder
+            .record(Message::Event(EventKind::from(status.clone()).into()));
+        self.handler.on_runtime_status(status)
+    }
+}
+
+/// Feeds recorded requests into a runtime and collects the produced events.
+///
+/// Each request is issued once the commands recorded as stopped before it have stopped
+/// in the replay as well. Once all requests are issued, the replay waits for the started
+/// commands to stop.
+pub struct Replay {
+    entries: Vec<RecordEntry>,
+    timeout: Duration,
+}
+
+impl Replay {
+    /// Default time limit of waiting for commands to stop
+    pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn new(entries: Vec<RecordEntry>) -> Self {
+        Self {
+            entries,
+            timeout: Self::TIMEOUT,
+        }
+    }
+
+    /// Read a recording file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).map_err(|e| Error::from(e).with_context("path", path.display()))?;
+
+        let mut entries = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line).map_err(|e| {
+                Error::invalid_argument(format!("Invalid recording entry: {}", e))
+                    .with_context("line", i + 1)
+            })?;
+            entries.push(entry);
+        }
+        Ok(Self::new(entries))
+    }
+
+    /// Set the time limit of waiting for commands to stop, after which
+    /// the replay continues and the missing events are reported
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Return recorded entries
+    pub fn entries(&self) -> &[RecordEntry] {
+        &self.entries
+    }
+
+    /// Replay recorded requests. Must be called within a `tokio::task::LocalSet`
+    pub async fn run<R>(&self, runtime: R, mut ctx: Context<R>) -> ReplayReport
+    where
+        R: Runtime + 'static,
+    {
+        let (tx, mut rx) = mpsc::unbounded();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        ctx.set_emitter(Collector {
+            events: events.clone(),
+            notify: tx,
+        });
+        let server = Server::new(runtime, ctx);
+
+        let mut report = ReplayReport::default();
+        let mut stopped = 0;
+        let mut started = 0;
+        for entry in self.entries.iter() {
+            match entry.message {
+                Message::Request(ref request) => {
+                    settle(&events, &mut rx, stopped, self.timeout).await;
+                    let response = call(&server, request.clone()).await;
+                    if let Response::RunProcess { .. } = response {
+                        started += 1;
+                    }
+                    report.actual_responses.push(response);
+                }
+                Message::Response(ref response) => report.expected_responses.push(response.clone()),
+                Message::Event(ref event) => {
+                    if event.is_stopped() {
+                        stopped += 1;
+                    }
+                    report.expected_events.push(event.clone());
+                }
+            }
+        }
+
+        settle(&events, &mut rx, stopped.max(started), self.timeout).await;
+        report.actual_events = std::mem::take(&mut *events.lock().unwrap());
+        report
+    }
+}
+
+/// Recorded and produced responses and events
+#[derive(Clone, Debug, Default)]
+pub struct ReplayReport {
+    pub expected_responses: Vec<Response>,
+    pub actual_responses: Vec<Response>,
+    pub expected_events: Vec<Event>,
+    pub actual_events: Vec<Event>,
+}
+
+impl ReplayReport {
+    /// Check whether the runtime reproduced the recorded responses and events
+    pub fn is_match(&self) -> bool {
+        self.diff().is_empty()
+    }
+
+    /// List differences between the recorded and produced responses and events.
+    /// Events of each command, and of the runtime itself, are compared in order of emission,
+    /// regardless of how events of concurrently executed commands were interleaved
+    pub fn diff(&self) -> Vec<String> {
+        let mut diff = diff_items("response", &self.expected_responses, &self.actual_responses);
+        let expected = group(&self.expected_events);
+        let actual = group(&self.actual_events);
+        let sources = expected
+            .keys()
+            .chain(actual.keys())
+            .collect::<BTreeSet<_>>();
+        for source in sources {
+            let kind = match source {
+                Some(pid) => format!("process {} event", pid),
+                None => "runtime event".to_string(),
+            };
+            let expected = expected.get(source).unwrap_or(&none);
+            let actual = actual.get(source).unwrap_or(&none);
+            diff.extend(diff_items(&kind, expected, actual));
+        }
+        diff
+    }
+}
+
+/// Group events by the emitting command, retaining their order
+fn group(events: &[Event]) -> BTreeMap<Option<ProcessId>, Vec<Event>> {
+    events.iter().fold(BTreeMap::new(), |mut groups, event| {
+        groups.entry(event.pid()).or_default().push(event.clone());
+        groups
+    })
+}
+
+fn diff_items<T: std::fmt::Debug + PartialEq>(
+    kind: &str,
+    expected: &[T],
+    actual: &[T],
+) -> Vec<String> {
+    let len = expected.len().max(actual.len());
+    (0..len)
+        .filter_map(|i| match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => None,
+            (Some(e), Some(a)) => Some(format!("{} #{}: expected {:?}, got {:?}", kind, i, e, a)),
+            (Some(e), None) => Some(format!("{} #{}: missing {:?}", kind, i, e)),
+            (None, Some(a)) => Some(format!("{} #{}: unexpected {:?}", kind, i, a)),
+            (None, None) => None,
+        })
+        .collect()
+}
+
+async fn call<R: Runtime + 'static>(server: &Server<R>, request: Request) -> Response {
+    let result = match request {
+        Request::Hello { version } => server
+            .hello(&version)
+            .await
+            .map(|version| Response::Hello { version }),
+        Request::RunProcess { .. } => server
+            .run_process(request.into())
+            .await
+            .map(|resp| Response::RunProcess { pid: resp.pid }),
+        Request::KillProcess { .. } => server
+            .kill_process(request.into())
+            .await
+            .map(|_| Response::KillProcess),
+        Request::CreateNetwork { .. } => server
+            .create_network(request.into())
+            .await
+            .map(|resp| Response::from(&resp)),
+        Request::Shutdown => server.shutdown().await.map(|_| Response::Shutdown),
+    };
+    result.unwrap_or_else(|err| Response::from(&err))
+}
+
+/// Wait until `count` commands have stopped, for at most `timeout`
+async fn settle(
+    events: &Mutex<Vec<Event>>,
+    notify: &mut mpsc::UnboundedReceiver<()>,
+    count: usize,
+    timeout: Duration,
+) {
+    let stopped = || {
+        events
+            .lock()
+            .map(|e| e.iter().filter(|e| e.is_stopped()).count())
+            .unwrap_or_default()
+    };
+    let wait = async {
+        while stopped() < count {
+            if notify.next().await.is_none() {
+                break;
+            }
+        }
+    };
+    let _ = tokio::time::timeout(timeout, wait).await;
+}
+
+struct Collector {
+    events: Arc<Mutex<Vec<Event>>>,
+    notify: mpsc::UnboundedSender<()>,
+}
+
+impl Collector {
+    fn push(&self, event: EventKind) -> BoxFuture<'static, ()> {
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event.into());
+        }
+        let _ = self.notify.unbounded_send(());
+        futures::future::ready(()).boxed()
+    }
+}
+
+impl RuntimeHandler for Collector {
+    fn on_process_status<'a>(&self, status: ProcessStatus) -> BoxFuture<'a, ()> {
+        self.push(status.into())
+    }
+
+    fn on_runtime_status<'a>(&self, status: RuntimeStatus) -> BoxFuture<'a, ()> {
+        self.push(status.into())
+    }
+}
+
+/// Return the recording file path within `workdir`
+pub(crate) fn path(workdir: impl AsRef<Path>) -> PathBuf {
+    workdir.as_ref().join(Recorder::FILE_NAME)
+}
+
+impl From<&RunProcess> for Request {
+    fn from(run: &RunProcess) -> Self {
+        let capture = |output: &Option<Output>| {
+            output
+                .as_ref()
+                .filter(|o| o.r#type.is_some())
+                .map(Capture::from)
+        };
+        Self::RunProcess {
+            bin: run.bin.clone(),
+            args: run.args.clone(),
+            work_dir: run.work_dir.clone(),
+            stdout: capture(&run.stdout),
+            stderr: capture(&run.stderr),
+        }
+    }
+}
+
+impl From<&KillProcess> for Request {
+    fn from(kill: &KillProcess) -> Self {
+        Self::KillProcess {
+            pid: kill.pid,
+            signal: kill.signal,
+        }
+    }
+}
+
+impl From<&CreateNetwork> for Request {
+    fn from(network: &CreateNetwork) -> Self {
+        Self::CreateNetwork {
+            networks: network
+                .networks
+                .iter()
+                .map(|n| NetworkDef {
+                    addr: n.addr.clone(),
+                    gateway: n.gateway.clone(),
+                    mask: n.mask.clone(),
+                    if_addr: n.if_addr.clone(),
+                })
+                .collect(),
+            hosts: network
+                .hosts
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            interface: network.interface,
+        }
+    }
+}
+
+impl From<Request> for RunProcess {
+    fn from(request: Request) -> Self {
+        let output = |capture: Option<Capture>| capture.map(Output::from);
+        match request {
+            Request::RunProcess {
+                bin,
+                args,
+                work_dir,
+                stdout,
+                stderr,
+            } => RunProcess {
+                bin,
+                args,
+                work_dir,
+                stdout: output(stdout),
+                stderr: output(stderr),
+            },
+            _ => Default::default(),
+        }
+    }
+}
+
+impl From<Request> for KillProcess {
+    fn from(request: Request) -> Self {
+        match request {
+            Request::KillProcess { pid, signal } => KillProcess { pid, signal },
+            _ => Default::default(),
+        }
+    }
+}
+
+impl From<Request> for CreateNetwork {
+    fn from(request: Request) -> Self {
+        match request {
+            Request::CreateNetwork {
+                networks,
+                hosts,
+                interface,
+            } => CreateNetwork {
+                networks: networks
+                    .into_iter()
+                    .map(|n| Network {
+                        addr: n.addr,
+                        gateway: n.gateway,
+                        mask: n.mask,
+                        if_addr: n.if_addr,
+                    })
+                    .collect(),
+                hosts: hosts.into_iter().collect(),
+                interface,
+            },
+            _ => Default::default(),
+        }
+    }
+}
+
+impl From<&ErrorResponse> for Response {
+    fn from(err: &ErrorResponse) -> Self {
+        Self::Error {
+            code: err.code,
+            message: err.message.clone(),
+            context: err
+                .context
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl From<&CreateNetworkResp> for Response {
+    fn from(resp: &CreateNetworkResp) -> Self {
+        Self::CreateNetwork {
+            endpoint: resp.endpoint.as_ref().map(|e| match e {
+                Endpoint::UnixStream(s)
+                | Endpoint::UnixDatagram(s)
+                | Endpoint::UdpDatagram(s)
+                | Endpoint::TcpStream(s) => s.clone(),
+            }),
+        }
+    }
+}
+
+impl From<EventKind> for Event {
+    fn from(event: EventKind) -> Self {
+        match event {
+            EventKind::Process(status) => Self::ProcessStatus {
+                pid: status.pid,
+                running: status.running,
+                return_code: status.return_code,
+                stdout: status.stdout,
+                stderr: status.stderr,
+            },
+            EventKind::Runtime(status) => match status.kind {
+                Some(RuntimeStatusKind::State(state)) => Self::State {
+                    name: state.name,
+                    value: state.value,
+                },
+                Some(RuntimeStatusKind::Counter(counter)) => Self::Counter {
+                    name: counter.name,
+                    value: counter.value,
+                },
+                None => Self::Empty,
+            },
+        }
+    }
+}
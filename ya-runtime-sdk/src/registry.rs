@@ -0,0 +1,113 @@
+use futures::channel::oneshot;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use crate::common::timestamp;
+use crate::error::Error;
+use crate::runtime::{CommandRecord, CommandState, ProcessId};
+
+/// In-memory command execution records
+#[derive(Clone, Default)]
+pub(crate) struct CommandRegistry {
+    records: Rc<RefCell<BTreeMap<ProcessId, CommandRecord>>>,
+    waiters: Rc<RefCell<HashMap<ProcessId, Vec<oneshot::Sender<Option<Error>>>>>>,
+}
+
+impl CommandRegistry {
+    /// Maximum number of retained records of finished commands
+    pub const MAX_FINISHED: usize = 1024;
+
+    pub fn get(&self, pid: ProcessId) -> Option<CommandRecord> {
+        self.records.borrow().get(&pid).cloned()
+    }
+
+    pub fn list(&self) -> Vec<CommandRecord> {
+        self.records.borrow().values().cloned().collect()
+    }
+
+    /// Update the command state and return the updated record
+    pub fn set_state(
+        &self,
+        pid: ProcessId,
+        state: CommandState,
+        return_code: Option<i32>,
+    ) -> CommandRecord {
+        let mut records = self.records.borrow_mut();
+        let record = records
+            .entry(pid)
+            .or_insert_with(|| CommandRecord::new(pid));
+        record.state = state;
+        record.return_code = return_code;
+        match state {
+            CommandState::Queued => (),
+            CommandState::Running => record.started_at = Some(timestamp()),
+            CommandState::Finished => record.finished_at = Some(timestamp()),
+        }
+        let record = record.clone();
+
+        if state == CommandState::Finished {
+            prune(&mut records);
+        }
+        record
+    }
+
+    /// Wait for a command to finish, receiving the error it failed with, if any.
+    /// Returns `None` for unknown and already finished commands
+    pub fn finished(&self, pid: ProcessId) -> Option<oneshot::Receiver<Option<Error>>> {
+        match self.get(pid) {
+            Some(record) if record.state != CommandState::Finished => {
+                let (tx, rx) = oneshot::channel();
+                self.waiters.borrow_mut().entry(pid).or_default().push(tx);
+                Some(rx)
+            }
+            _ => None,
+        }
+    }
+
+    /// Notify the waiters of a finished command
+    pub fn finish(&self, pid: ProcessId, error: Option<Error>) {
+        let waiters = self.waiters.borrow_mut().remove(&pid);
+        for tx in waiters.unwrap_or_default() {
+            let _ = tx.send(error.clone());
+        }
+    }
+
+    /// Append command output to the captured output tail
+    pub fn output(&self, pid: ProcessId, stdout: &[u8], stderr: &[u8]) {
+        let mut records = self.records.borrow_mut();
+        let record = records
+            .entry(pid)
+            .or_insert_with(|| CommandRecord::new(pid));
+        append_tail(&mut record.stdout, stdout);
+        append_tail(&mut record.stderr, stderr);
+    }
+}
+
+/// Append output to the tail, retaining at most `CommandRecord::OUTPUT_TAIL` bytes.
+/// The tail does not start within a UTF-8 encoded character, keeping text output valid
+fn append_tail(tail: &mut Vec<u8>, output: &[u8]) {
+    tail.extend_from_slice(output);
+    if tail.len() > CommandRecord::OUTPUT_TAIL {
+        let mut start = tail.len() - CommandRecord::OUTPUT_TAIL;
+        // skip continuation bytes of a character, which are at most 3
+        let end = (start + 3).min(tail.len());
+        while start < end && tail[start] & 0xC0 == 0x80 {
+            start += 1;
+        }
+        tail.drain(..start);
+    }
+}
+
+/// Remove records of the earliest finished commands
+fn prune(records: &mut BTreeMap<ProcessId, CommandRecord>) {
+    let finished = records
+        .values()
+        .filter(|r| r.state == CommandState::Finished)
+        .map(|r| r.pid)
+        .collect::<Vec<_>>();
+    let excess = finished.len().saturating_sub(CommandRegistry::MAX_FINISHED);
+    for pid in finished.into_iter().take(excess) {
+        records.remove(&pid);
+    }
+}
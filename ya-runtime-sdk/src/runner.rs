@@ -1,15 +1,22 @@
-use futures::future::LocalBoxFuture;
+use futures::channel::oneshot;
+use futures::future::{Either, LocalBoxFuture};
 use futures::FutureExt;
+use std::any::Any;
 use std::future::Future;
+use std::panic::AssertUnwindSafe;
 
 use ya_runtime_api::server::proto::{output::Type, request::RunProcess, Output};
 
 use crate::cli::{Command, CommandCli};
-use crate::common::write_output;
+use crate::common::panic_message;
 use crate::context::Context;
 use crate::env::{DefaultEnv, Env};
+use crate::error::Error;
+use crate::output::OutputWriter;
+use crate::plugin::{PluginDescriptor, Preset};
 use crate::runtime::{Runtime, RuntimeDef, RuntimeMode};
-use crate::server::Server;
+use crate::server::{self, Server};
+use crate::trace;
 
 /// Starts the runtime within a new `tokio::task::LocalSet`
 #[inline]
@@ -49,31 +56,79 @@ where
     .boxed_local()
 }
 
-async fn inner<R, E, F>(env: E, factory: F) -> anyhow::Result<()>
+async fn inner<R, E, F>(mut env: E, factory: F) -> anyhow::Result<()>
 where
     R: Runtime + 'static,
     E: Env<<R as RuntimeDef>::Cli> + Send + 'static,
     F: FnOnce(&mut Context<R>) -> LocalBoxFuture<anyhow::Result<R>>,
 {
-    #[cfg(feature = "logger")]
-    {
-        if let Err(error) = crate::logger::start_file_logger() {
-            crate::logger::start_logger().expect("Failed to start logging");
-            log::warn!("Using fallback logging due to an error: {:?}", error);
-        };
-
-        std::panic::set_hook(Box::new(|e| {
-            log::error!("Runtime panic: {e}");
-        }));
+    // the Runtime API is served over stdout in `Server` mode
+    let (report, pretty) = match env.cli(R::NAME, R::VERSION) {
+        Ok(cli) => {
+            let report = !matches!(
+                (cli.command(), R::MODE),
+                (Command::Start { .. }, RuntimeMode::Server)
+            );
+            (report, cli.pretty())
+        }
+        Err(_) => (true, false),
+    };
+    let output = OutputWriter::new(env.stdout(), pretty);
+
+    let result = match Context::<R>::try_with(env) {
+        Ok(ctx) => {
+            #[cfg(feature = "logger")]
+            let _logger = {
+                let name = ctx
+                    .env
+                    .runtime_name()
+                    .unwrap_or_else(|| R::NAME.to_string());
+                let guard = crate::logger::start(ctx.env.log_directory(name.as_str()));
+
+                std::panic::set_hook(Box::new(|e| {
+                    log::error!("Runtime panic: {e}");
+                }));
+                guard
+            };
+
+            execute(ctx, factory).await
+        }
+        Err(err) => {
+            // the logger is not started yet
+            eprintln!("Unable to initialize the runtime: {:?}", err);
+            Err(err)
+        }
+    };
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) if !report || err.is::<Reported>() => Err(err),
+        Err(err) => {
+            let err = Error::from(err);
+            output.json(&err)?;
+            Err(err.into())
+        }
     }
+}
 
-    let mut ctx = Context::<R>::try_with(env)?;
+async fn execute<R, F>(mut ctx: Context<R>, factory: F) -> anyhow::Result<()>
+where
+    R: Runtime + 'static,
+    F: FnOnce(&mut Context<R>) -> LocalBoxFuture<anyhow::Result<R>>,
+{
     let mut runtime = factory(&mut ctx).await?;
 
     match ctx.cli.command() {
         Command::Deploy { .. } => {
-            let deployment = match runtime.deploy(&mut ctx).await? {
-                Some(deployment) => deployment,
+            let deployment = phase("deploy", || runtime.deploy(&mut ctx)).await?;
+            let vols = ctx.deploy_volumes()?;
+            let deployment = match deployment {
+                Some(mut deployment) => {
+                    if let Some(obj) = deployment.as_object_mut() {
+                        obj.entry("vols").or_insert(vols);
+                    }
+                    deployment
+                }
                 None => {
                     crate::serialize::json::json!({
                         "startMode": match R::MODE {
@@ -81,38 +136,56 @@ where
                             RuntimeMode::Command => "empty",
                         },
                         "valid": {"Ok": ""},
-                        "vols": []
+                        "vols": vols
                     })
                 }
             };
-            write_output(deployment).await?;
+            ctx.output.json(&deployment)?;
         }
         Command::Start { .. } => match R::MODE {
             RuntimeMode::Command => {
-                if let Some(started) = runtime.start(&mut ctx).await? {
-                    write_output(started).await?;
+                if let Some(started) = phase("start", || runtime.start(&mut ctx)).await? {
+                    ctx.output.json(&started)?;
                 }
             }
             RuntimeMode::Server => {
-                ya_runtime_api::server::run_async(|emitter| async move {
-                    let start = {
-                        ctx.set_emitter(emitter);
-                        runtime.start(&mut ctx)
-                    };
+                if let Some(checkpoints) = ctx.checkpoints() {
+                    if let Err(_e) = (checkpoints.restore)(&mut ctx, &mut runtime) {
+                        #[cfg(feature = "logger")]
+                        log::warn!("Unable to restore a checkpoint, starting fresh: {}", _e);
+                    }
+                }
 
-                    match start.await {
+                let (failure_tx, failure_rx) = oneshot::channel();
+                ctx.set_failure_tx(failure_tx);
+
+                let serve = ya_runtime_api::server::run_async(|emitter| async move {
+                    ctx.set_emitter(emitter);
+
+                    match phase("start", || runtime.start(&mut ctx)).await {
                         Ok(Some(out)) => {
-                            ctx.next_run_ctx().stdout(out.to_string()).await;
+                            ctx.next_run_ctx(None).stdout(out.to_string()).await;
                         }
                         Err(err) => {
-                            panic!("Failed to start the runtime: {}", err);
+                            if let Some(emitter) = ctx.emitter.clone().filter(|_| ctx.report_panics)
+                            {
+                                server::report_error(emitter, err.message()).await;
+                            }
+                            ctx.control.fail(err)
                         }
                         _ => (),
                     }
 
                     Server::new(runtime, ctx)
-                })
-                .await;
+                });
+                futures::pin_mut!(serve);
+
+                // a failed start or a panicking service call terminates the runtime
+                if let Either::Right((Ok(err), _)) =
+                    futures::future::select(serve, failure_rx).await
+                {
+                    return Err(err.into());
+                }
             }
         },
         Command::Run { args } => {
@@ -126,7 +199,7 @@ where
                 r#type: Some(Type::AtEnd(40960)),
             });
             let command = RunProcess {
-                bin,
+                bin: bin.clone(),
                 args,
                 work_dir: ctx
                     .cli
@@ -138,21 +211,124 @@ where
                 stderr: capture,
             };
 
-            let pid = runtime
-                .run_command(command, RuntimeMode::Command, &mut ctx)
-                .await?;
+            let pid = phase("run", || {
+                let run = runtime.run_command(command, RuntimeMode::Command, &mut ctx);
+                trace::run_process(&bin, run)
+            })
+            .await?;
+
+            match R::MODE {
+                RuntimeMode::Command => {
+                    // the invocation ends once the started command finishes
+                    if let Some(finished) = ctx.registry.finished(pid) {
+                        if let Ok(Some(error)) = finished.await {
+                            ctx.output.json(&error)?;
+                        }
+                    }
 
-            if let RuntimeMode::Server = R::MODE {
-                write_output(serde_json::json!(pid)).await?;
+                    let record = ctx.command_status(pid)?;
+                    match record.and_then(|r| r.return_code) {
+                        Some(0) | None => (),
+                        Some(code) => {
+                            let message = format!("Command failed with return code {}", code);
+                            return Err(Reported { message, code }.into());
+                        }
+                    }
+                }
+                RuntimeMode::Server => ctx.output.json(&pid)?,
             }
         }
         Command::OfferTemplate { .. } => {
-            if let Some(template) = runtime.offer(&mut ctx).await? {
-                write_output(template).await?;
+            if let Some(template) = phase("offer", || runtime.offer(&mut ctx)).await? {
+                ctx.output.json(&template)?;
+            }
+        }
+        Command::PluginDescriptor {
+            description,
+            supervisor_path,
+            preset,
+            args,
+        } => {
+            let name = ctx
+                .env
+                .runtime_name()
+                .unwrap_or_else(|| R::NAME.to_string());
+            let runtime_path = std::env::current_exe()?;
+
+            let mut descriptor = PluginDescriptor::new(&name, R::VERSION, runtime_path);
+            if let Some(description) = description {
+                descriptor.description = description.clone();
+            }
+            if let Some(supervisor_path) = supervisor_path {
+                descriptor.supervisor_path = supervisor_path.clone();
+            }
+            descriptor.extra_args = args.clone();
+
+            let output = match preset {
+                true => serde_json::json!({
+                    "descriptor": [descriptor],
+                    "preset": Preset::new(&name, R::COUNTERS.iter().copied()),
+                }),
+                false => serde_json::json!([descriptor]),
+            };
+            ctx.output.json(&output)?;
+        }
+        Command::Test { .. } => {
+            let report = phase("test", || runtime.self_test(&mut ctx).run().map(Ok)).await?;
+            ctx.output.json(&report)?;
+
+            if !report.passed {
+                let failed = report.failed().collect::<Vec<_>>();
+                let message = format!("Self-test failed: {}", failed.join(", "));
+                return Err(Reported { message, code: 1 }.into());
             }
         }
-        Command::Test { .. } => runtime.test(&mut ctx).await?,
     }
 
     Ok(())
 }
+
+/// Runs a runtime execution phase, turning a panic into an error
+async fn phase<T, F>(phase: &'static str, f: impl FnOnce() -> F) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    let fut = std::panic::catch_unwind(AssertUnwindSafe(f)).map_err(panicked)?;
+    let fut = AssertUnwindSafe(trace::phase(phase, fut)).catch_unwind();
+    fut.await.map_err(panicked)?
+}
+
+fn panicked(payload: Box<dyn Any + Send>) -> Error {
+    Error::internal(format!(
+        "Runtime panic: {}",
+        panic_message(payload.as_ref())
+    ))
+}
+
+/// Process exit code of a failed runtime execution: the return code of a command
+/// invoked via command line, the code of an `Error` within the range of exit codes,
+/// or 1 otherwise
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    if let Some(reported) = error.downcast_ref::<Reported>() {
+        return reported.code;
+    }
+    match error.downcast_ref::<Error>().map(Error::code) {
+        Some(code) if (1..=255).contains(&code) => code,
+        _ => 1,
+    }
+}
+
+/// Failure already reported on stdout, e.g. by a command invoked via command line
+#[derive(Debug)]
+struct Reported {
+    message: String,
+    code: i32,
+}
+
+impl std::fmt::Display for Reported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Reported {}
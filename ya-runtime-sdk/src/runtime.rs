@@ -1,5 +1,6 @@
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::time::Duration;
 
 use futures::channel::oneshot;
 use futures::future::LocalBoxFuture;
@@ -10,10 +11,22 @@ use crate::cli::CommandCli;
 use crate::context::Context;
 use crate::error::Error;
 use crate::runtime_api::server::*;
+use crate::self_test::{Check, CheckOutcome, SelfTest};
 
 use ya_runtime_api::deploy::ContainerEndpoint;
 
 pub type ProcessId = u64;
+
+/// Return code of a command terminated by a panic
+pub const RETURN_CODE_PANIC: i32 = 101;
+/// Return code of a process terminated for exceeding its CPU time limit
+pub const RETURN_CODE_CPU_LIMIT: i32 = 152;
+/// Return code of a process terminated for exceeding its file size limit
+pub const RETURN_CODE_FILE_SIZE_LIMIT: i32 = 153;
+/// Return code of a process terminated for exceeding its memory limit
+pub const RETURN_CODE_MEMORY_LIMIT: i32 = 154;
+/// Return code of a command cancelled after exceeding its execution timeout
+pub const RETURN_CODE_TIMEOUT: i32 = 124;
 pub type EmptyResponse<'a> = LocalBoxFuture<'a, Result<(), Error>>;
 pub type OutputResponse<'a> = LocalBoxFuture<'a, Result<Option<serde_json::Value>, Error>>;
 pub type EndpointResponse<'a> = LocalBoxFuture<'a, Result<ContainerEndpoint, Error>>;
@@ -21,7 +34,9 @@ pub type ProcessIdResponse<'a> = LocalBoxFuture<'a, Result<ProcessId, Error>>;
 
 /// Command handling interface for runtimes
 pub trait Runtime: RuntimeDef {
-    const MODE: RuntimeMode = RuntimeMode::Server;
+    const MODE: RuntimeMode = <Self as RuntimeDef>::DEFAULT_MODE;
+    /// Names of usage counters emitted by the runtime
+    const COUNTERS: &'static [&'static str] = &[];
 
     /// Deploy and configure the runtime
     fn deploy<'a>(&mut self, ctx: &mut Context<Self>) -> OutputResponse<'a>;
@@ -42,13 +57,26 @@ pub trait Runtime: RuntimeDef {
         ctx: &mut Context<Self>,
     ) -> ProcessIdResponse<'a>;
 
+    /// Return the execution timeout of `command`, overriding `Context::command_timeout`.
+    /// Applied to handlers started with `Context::command_for`
+    fn command_timeout(_command: &RunProcess, ctx: &Context<Self>) -> Option<Duration> {
+        ctx.command_timeout
+    }
+
+    /// Return the priority of `command`, applied when queued with `QueueOrder::Priority`.
+    /// Commands with higher values are started first.
+    /// Applied to handlers started with `Context::command_for`
+    fn command_priority(_command: &RunProcess, _ctx: &Context<Self>) -> i32 {
+        0
+    }
+
     /// Stop runtime command execution
     fn kill_command<'a>(
         &mut self,
         _kill: KillProcess,
         _ctx: &mut Context<Self>,
     ) -> EmptyResponse<'a> {
-        async move { Err(Error::from_string("Not supported")) }.boxed_local()
+        async move { Err(Error::not_supported("Not supported")) }.boxed_local()
     }
 
     /// Output a market Offer template stub
@@ -67,13 +95,22 @@ pub trait Runtime: RuntimeDef {
         async move { Ok(()) }.boxed_local()
     }
 
+    /// Register self-test checks, performed by the `test` command.
+    /// Defaults to a single check, executing `Runtime::test`
+    fn self_test(&mut self, ctx: &mut Context<Self>) -> SelfTest {
+        let test = self.test(ctx);
+        SelfTest::new().check(Check::new("test", async move {
+            test.await.map(|_| CheckOutcome::default())
+        }))
+    }
+
     /// Join a VPN network
     fn join_network<'a>(
         &mut self,
         _network: CreateNetwork,
         _ctx: &mut Context<Self>,
     ) -> EndpointResponse<'a> {
-        async move { Err(Error::from_string("Not supported")) }.boxed_local()
+        async move { Err(Error::not_supported("Not supported")) }.boxed_local()
     }
 }
 
@@ -82,6 +119,8 @@ pub trait Runtime: RuntimeDef {
 pub trait RuntimeDef {
     const NAME: &'static str;
     const VERSION: &'static str;
+    /// Execution mode used by `Runtime::MODE`, unless overridden
+    const DEFAULT_MODE: RuntimeMode = RuntimeMode::Server;
 
     type Cli: CommandCli;
     type Conf: Default + Serialize + for<'de> Deserialize<'de>;
@@ -99,10 +138,61 @@ pub enum RuntimeMode {
     Command,
 }
 
+/// Command execution state
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CommandState {
+    /// Waiting for a `Scheduler` execution slot
+    Queued,
+    Running,
+    Finished,
+}
+
+/// Command execution record, maintained by the command lifecycle wrapper
+/// and persisted in `RuntimeMode::Command`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandRecord {
+    pub pid: ProcessId,
+    pub state: CommandState,
+    pub return_code: Option<i32>,
+    /// Start time, in milliseconds since the Unix epoch
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    /// End time, in milliseconds since the Unix epoch
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    /// Last `CommandRecord::OUTPUT_TAIL` bytes of stdout, as emitted in output events,
+    /// i.e. encoded with the stdout encoding of `RunCommandContext`
+    #[serde(default, with = "crate::common::bytes")]
+    pub stdout: Vec<u8>,
+    /// Last `CommandRecord::OUTPUT_TAIL` bytes of stderr, as emitted in output events
+    #[serde(default, with = "crate::common::bytes")]
+    pub stderr: Vec<u8>,
+}
+
+impl CommandRecord {
+    /// Maximum size of the captured output tail of each stream
+    pub const OUTPUT_TAIL: usize = 4096;
+
+    pub(crate) fn new(pid: ProcessId) -> Self {
+        Self {
+            pid,
+            state: CommandState::Queued,
+            return_code: None,
+            started_at: None,
+            finished_at: None,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        }
+    }
+}
+
 /// Runtime control helper
 #[derive(Clone, Default)]
 pub struct RuntimeControl {
     pub(crate) shutdown_tx: Rc<RefCell<Option<oneshot::Sender<()>>>>,
+    pub(crate) failure_tx: Rc<RefCell<Option<oneshot::Sender<Error>>>>,
 }
 
 impl RuntimeControl {
@@ -111,4 +201,11 @@ impl RuntimeControl {
             let _ = tx.send(());
         }
     }
+
+    /// Terminate the runtime with an error
+    pub(crate) fn fail(&self, error: Error) {
+        if let Some(tx) = self.failure_tx.borrow_mut().take() {
+            let _ = tx.send(error);
+        }
+    }
 }
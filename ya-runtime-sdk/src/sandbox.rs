@@ -0,0 +1,518 @@
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::error::Error;
+use crate::volumes::Volumes;
+
+/// Isolation settings of processes spawned with `RunCommandContext::spawn`
+#[derive(Clone, Debug)]
+pub struct SandboxPolicy {
+    /// Run in a new user namespace, mapping the current user to root
+    pub user_namespace: bool,
+    /// Run in a new PID namespace
+    pub pid_namespace: bool,
+    /// Run in a new network namespace, with no network access
+    pub network_namespace: bool,
+    /// Root directory of the process. Defaults to the root of the host filesystem
+    pub root: Option<PathBuf>,
+    /// Mount the root directory and all of its submounts read-only
+    pub read_only_root: bool,
+    /// Directories bind-mounted writable within the root directory. Mount points
+    /// must exist within the root directory; creating them on the host is not supported
+    pub mounts: Vec<Mount>,
+    /// System call filter
+    pub seccomp: Option<SeccompPolicy>,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self {
+            user_namespace: true,
+            pid_namespace: true,
+            network_namespace: true,
+            root: None,
+            read_only_root: true,
+            mounts: Vec::new(),
+            seccomp: None,
+        }
+    }
+}
+
+impl SandboxPolicy {
+    /// Bind-mount `source` directory at `target`, relative to the root directory
+    pub fn mount(mut self, source: impl Into<PathBuf>, target: impl Into<PathBuf>) -> Self {
+        self.mounts.push(Mount {
+            source: source.into(),
+            target: target.into(),
+        });
+        self
+    }
+
+    /// Bind-mount volume directories of `workdir` at their paths inside the runtime
+    pub fn volumes(self, volumes: &Volumes, workdir: impl AsRef<Path>) -> Self {
+        volumes.iter().fold(self, |policy, volume| {
+            policy.mount(workdir.as_ref().join(&volume.name), &volume.path)
+        })
+    }
+}
+
+/// Writable bind mount
+#[derive(Clone, Debug)]
+pub struct Mount {
+    /// Host directory
+    pub source: PathBuf,
+    /// Mount point, relative to the sandbox root directory
+    pub target: PathBuf,
+}
+
+/// Seccomp filter, denying the listed system calls
+#[derive(Clone, Debug)]
+pub struct SeccompPolicy {
+    /// Denied system call numbers
+    pub deny: Vec<i64>,
+    /// Error number returned by denied system calls
+    pub errno: i32,
+}
+
+impl Default for SeccompPolicy {
+    /// Deny system calls used to escape or tamper with the sandbox and the host
+    #[cfg(target_os = "linux")]
+    #[allow(clippy::unnecessary_cast)] // `c_long` is 32-bit wide on some platforms
+    fn default() -> Self {
+        let deny = [
+            libc::SYS_ptrace,
+            libc::SYS_mount,
+            libc::SYS_umount2,
+            libc::SYS_pivot_root,
+            libc::SYS_unshare,
+            libc::SYS_setns,
+            libc::SYS_reboot,
+            libc::SYS_kexec_load,
+            libc::SYS_init_module,
+            libc::SYS_finit_module,
+            libc::SYS_delete_module,
+            libc::SYS_swapon,
+            libc::SYS_swapoff,
+            libc::SYS_bpf,
+            libc::SYS_perf_event_open,
+            libc::SYS_keyctl,
+            libc::SYS_add_key,
+            libc::SYS_request_key,
+        ];
+        Self {
+            deny: deny.iter().map(|nr| *nr as i64).collect(),
+            errno: libc::EPERM,
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn default() -> Self {
+        Self {
+            deny: Vec::new(),
+            errno: 1,
+        }
+    }
+}
+
+/// Run `command` in a sandbox, configured by `policy`
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn apply(_command: &mut Command, _policy: &SandboxPolicy) -> Result<(), Error> {
+    Err(Error::not_supported("Sandbox is only supported on Linux"))
+}
+
+/// Run `command` in a sandbox, configured by `policy`.
+/// Must be called after other `pre_exec` hooks are registered
+#[cfg(target_os = "linux")]
+pub(crate) fn apply(command: &mut Command, policy: &SandboxPolicy) -> Result<(), Error> {
+    let sandbox = linux::Sandbox::new(policy)?;
+    // SAFETY: `Sandbox::enter` only performs async-signal-safe system calls
+    unsafe { command.pre_exec(move || sandbox.enter()) };
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    use super::{SandboxPolicy, SeccompPolicy};
+    use crate::error::Error;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: Option<u32> = Some(0xC000_003E);
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: Option<u32> = Some(0xC000_00B7);
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    const AUDIT_ARCH: Option<u32> = None;
+    /// Set in `nr` of x32 ABI system calls, which share the x86_64 architecture value
+    #[cfg(target_arch = "x86_64")]
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    /// Sandbox setup, prepared prior to forking the child process
+    pub struct Sandbox {
+        flags: libc::c_int,
+        id_maps: Option<[(CString, CString); 3]>,
+        root: Option<CString>,
+        mounts: Vec<(CString, CString)>,
+        read_only: Vec<CString>,
+        proc: Option<CString>,
+        seccomp: Option<Vec<libc::sock_filter>>,
+    }
+
+    impl Sandbox {
+        pub fn new(policy: &SandboxPolicy) -> Result<Self, Error> {
+            let mut flags = libc::CLONE_NEWNS;
+            if policy.user_namespace {
+                flags |= libc::CLONE_NEWUSER;
+            }
+            if policy.pid_namespace {
+                flags |= libc::CLONE_NEWPID;
+            }
+            if policy.network_namespace {
+                flags |= libc::CLONE_NEWNET;
+            }
+
+            let id_maps = if policy.user_namespace {
+                let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+                Some([
+                    (cstr("/proc/self/setgroups")?, cstr("deny")?),
+                    (cstr("/proc/self/uid_map")?, cstr(format!("0 {} 1", uid))?),
+                    (cstr("/proc/self/gid_map")?, cstr(format!("0 {} 1", gid))?),
+                ])
+            } else {
+                None
+            };
+
+            let root_path = policy.root.clone().unwrap_or_else(|| "/".into());
+            let targets = policy
+                .mounts
+                .iter()
+                .map(|m| {
+                    let target = mount_point(&root_path, &m.target)?;
+                    if policy.root.is_none() && !target.is_dir() {
+                        return Err(Error::invalid_argument(
+                            "Mount point does not exist on the host, a root directory is required",
+                        )
+                        .with_context("target", m.target.display()));
+                    }
+                    Ok(target)
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            let mounts = policy
+                .mounts
+                .iter()
+                .zip(targets.iter())
+                .map(|(m, target)| Ok((path_cstr(&m.source)?, path_cstr(target)?)))
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            let proc = mount_point(&root_path, Path::new("proc"))?;
+            let proc = match policy.pid_namespace && proc.is_dir() {
+                true => Some(path_cstr(&proc)?),
+                false => None,
+            };
+
+            let seccomp = match policy.seccomp {
+                Some(ref seccomp) => Some(filter(seccomp)?),
+                None => None,
+            };
+
+            Ok(Self {
+                flags,
+                id_maps,
+                root: match policy.root {
+                    Some(ref root) => Some(path_cstr(root)?),
+                    None => None,
+                },
+                mounts,
+                read_only: match policy.read_only_root {
+                    true => submounts(&root_path, &targets)?,
+                    false => Vec::new(),
+                },
+                proc,
+                seccomp,
+            })
+        }
+
+        /// Executed in the child process, prior to `exec`
+        pub fn enter(&self) -> io::Result<()> {
+            unsafe {
+                check(libc::unshare(self.flags))?;
+
+                if let Some(ref id_maps) = self.id_maps {
+                    for (path, contents) in id_maps.iter() {
+                        write_file(path, contents)?;
+                    }
+                }
+
+                let none = std::ptr::null();
+                let flags = libc::MS_REC | libc::MS_PRIVATE;
+                check(libc::mount(
+                    none,
+                    b"/\0".as_ptr() as _,
+                    none,
+                    flags,
+                    none as _,
+                ))?;
+                if let Some(ref root) = self.root {
+                    bind(root, root)?;
+                }
+                for (source, target) in self.mounts.iter() {
+                    bind(source, target)?;
+                }
+                for path in self.read_only.iter() {
+                    match remount_read_only(path) {
+                        // no longer a reachable mount point, e.g. covered by another mount
+                        Err(e)
+                            if matches!(
+                                e.raw_os_error(),
+                                Some(libc::EINVAL) | Some(libc::ENOENT) | Some(libc::EACCES)
+                            ) => {}
+                        result => result?,
+                    }
+                }
+
+                if self.flags & libc::CLONE_NEWPID != 0 {
+                    fork_init()?;
+                    if let Some(ref proc) = self.proc {
+                        let proc_type = b"proc\0".as_ptr() as *const libc::c_char;
+                        let flags = libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC;
+                        // /proc of the host remains available otherwise
+                        let _ = libc::mount(
+                            proc_type,
+                            proc.as_ptr(),
+                            proc_type,
+                            flags,
+                            std::ptr::null(),
+                        );
+                    }
+                }
+
+                if let Some(ref root) = self.root {
+                    check(libc::chroot(root.as_ptr()))?;
+                    check(libc::chdir(b"/\0".as_ptr() as _))?;
+                }
+
+                if let Some(ref filter) = self.seccomp {
+                    let prog = libc::sock_fprog {
+                        len: filter.len() as _,
+                        filter: filter.as_ptr() as *mut _,
+                    };
+                    check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+                    check(libc::prctl(
+                        libc::PR_SET_SECCOMP,
+                        libc::SECCOMP_MODE_FILTER,
+                        &prog as *const libc::sock_fprog,
+                    ))?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Fork the first process of the new PID namespace. The current process waits
+    /// for it to exit and forwards its exit status
+    unsafe fn fork_init() -> io::Result<()> {
+        match check(libc::fork())? {
+            0 => {
+                check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL))?;
+                Ok(())
+            }
+            pid => {
+                let mut status = 0;
+                while libc::waitpid(pid, &mut status, 0) < 0 {
+                    if io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+                        libc::_exit(1);
+                    }
+                }
+                if libc::WIFSIGNALED(status) {
+                    let signal = libc::WTERMSIG(status);
+                    libc::signal(signal, libc::SIG_DFL);
+                    libc::kill(libc::getpid(), signal);
+                    libc::_exit(128 + signal);
+                }
+                libc::_exit(libc::WEXITSTATUS(status))
+            }
+        }
+    }
+
+    unsafe fn bind(source: &CString, target: &CString) -> io::Result<()> {
+        let none = std::ptr::null();
+        let flags = libc::MS_BIND | libc::MS_REC;
+        check(libc::mount(
+            source.as_ptr(),
+            target.as_ptr(),
+            none,
+            flags,
+            none as _,
+        ))?;
+        Ok(())
+    }
+
+    /// Remount `path` read-only, retaining flags locked by the parent user namespace
+    unsafe fn remount_read_only(path: &CString) -> io::Result<()> {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        check(libc::statvfs(path.as_ptr(), &mut stat))?;
+
+        let mut flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY;
+        let retained = [
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NOATIME, libc::MS_NOATIME),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+            (libc::ST_RELATIME, libc::MS_RELATIME),
+        ];
+        for (st, ms) in retained.iter() {
+            if stat.f_flag & *st != 0 {
+                flags |= *ms;
+            }
+        }
+
+        let none = std::ptr::null();
+        check(libc::mount(none, path.as_ptr(), none, flags, none as _))?;
+        Ok(())
+    }
+
+    /// Mount points of `root` and its submounts, except for those covered by bind mounts at `targets`
+    fn submounts(root: &Path, targets: &[PathBuf]) -> Result<Vec<CString>, Error> {
+        let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+        let canonical = root.canonicalize()?;
+        let targets = targets
+            .iter()
+            .map(|target| target.canonicalize().unwrap_or_else(|_| target.clone()))
+            .collect::<Vec<_>>();
+
+        let mut paths = vec![root.to_path_buf()];
+        for line in mountinfo.lines() {
+            // the mount point is the 5th field
+            let path = match line.split(' ').nth(4) {
+                Some(field) => unescape(field),
+                None => continue,
+            };
+            if path == canonical || !path.starts_with(&canonical) || paths.contains(&path) {
+                continue;
+            }
+            if targets.iter().any(|target| path.starts_with(target)) {
+                continue;
+            }
+            paths.push(path);
+        }
+        paths.iter().map(|path| path_cstr(path)).collect()
+    }
+
+    /// Decode octal escape sequences (e.g. `\040` for a space) of a `mountinfo` field
+    fn unescape(field: &str) -> PathBuf {
+        use std::ffi::OsStr;
+
+        let bytes = field.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let octal = bytes
+                .get(i + 1..i + 4)
+                .and_then(|octal| std::str::from_utf8(octal).ok())
+                .and_then(|octal| u8::from_str_radix(octal, 8).ok());
+            match (bytes[i], octal) {
+                (b'\\', Some(byte)) => {
+                    decoded.push(byte);
+                    i += 4;
+                }
+                (byte, _) => {
+                    decoded.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        PathBuf::from(OsStr::from_bytes(&decoded))
+    }
+
+    unsafe fn write_file(path: &CString, contents: &CString) -> io::Result<()> {
+        let fd = check(libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC))?;
+        let bytes = contents.as_bytes();
+        let written = libc::write(fd, bytes.as_ptr() as _, bytes.len());
+        libc::close(fd);
+        match written as usize == bytes.len() {
+            true => Ok(()),
+            false => Err(io::Error::last_os_error()),
+        }
+    }
+
+    fn check<T: Default + PartialOrd>(result: T) -> io::Result<T> {
+        match result < T::default() {
+            true => Err(io::Error::last_os_error()),
+            false => Ok(result),
+        }
+    }
+
+    /// Build a BPF program returning `errno` for denied system calls
+    fn filter(policy: &SeccompPolicy) -> Result<Vec<libc::sock_filter>, Error> {
+        let arch = AUDIT_ARCH
+            .ok_or_else(|| Error::not_supported("Seccomp is not supported on this architecture"))?;
+        if policy.deny.len() >= u8::MAX as usize {
+            return Err(Error::invalid_argument("Too many denied system calls"));
+        }
+
+        let stmt = |code: u32, k: u32| libc::sock_filter {
+            code: code as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        };
+        let jump = |op: u32, k: u32, jt: u8, jf: u8| libc::sock_filter {
+            code: (libc::BPF_JMP | op | libc::BPF_K) as u16,
+            jt,
+            jf,
+            k,
+        };
+        let load = |offset: u32| stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, offset);
+        let ret = |value: u32| stmt(libc::BPF_RET | libc::BPF_K, value);
+
+        let deny = ret(libc::SECCOMP_RET_ERRNO | (policy.errno as u32 & libc::SECCOMP_RET_DATA));
+        let count = policy.deny.len() as u8;
+
+        // `seccomp_data` layout: `nr` at offset 0, `arch` at offset 4
+        let mut program = vec![
+            load(4),
+            jump(libc::BPF_JEQ, arch, 1, 0),
+            ret(libc::SECCOMP_RET_KILL_PROCESS),
+            load(0),
+        ];
+        // x32 system calls would not match any of the denied numbers
+        #[cfg(target_arch = "x86_64")]
+        program.push(jump(libc::BPF_JGE, X32_SYSCALL_BIT, count + 1, 0));
+        for (i, nr) in policy.deny.iter().enumerate() {
+            // jump to the `deny` statement on match
+            program.push(jump(libc::BPF_JEQ, *nr as u32, count - i as u8, 0));
+        }
+        program.push(ret(libc::SECCOMP_RET_ALLOW));
+        program.push(deny);
+        Ok(program)
+    }
+
+    fn mount_point(root: &Path, target: &Path) -> Result<PathBuf, Error> {
+        use std::path::Component;
+
+        let mut path = root.to_path_buf();
+        for component in target.components() {
+            match component {
+                Component::RootDir | Component::CurDir => (),
+                Component::Normal(name) => path.push(name),
+                _ => {
+                    return Err(Error::invalid_argument("Invalid mount point")
+                        .with_context("target", target.display()))
+                }
+            }
+        }
+        Ok(path)
+    }
+
+    fn cstr(s: impl Into<Vec<u8>>) -> Result<CString, Error> {
+        CString::new(s).map_err(|e| Error::invalid_argument(e.to_string()))
+    }
+
+    fn path_cstr(path: &Path) -> Result<CString, Error> {
+        cstr(path.as_os_str().as_bytes())
+    }
+}
@@ -0,0 +1,196 @@
+use futures::channel::oneshot;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::error::Error;
+
+/// Order of queued commands
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum QueueOrder {
+    /// Start commands in the order of requests
+    #[default]
+    Fifo,
+    /// Start commands with the highest priority first; commands of equal priority
+    /// are started in the order of requests
+    Priority,
+}
+
+/// Number of running and queued commands, emitted as a `RuntimeState` event
+/// named `Scheduler::STATE` whenever a command is queued or leaves the queue
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct QueueDepth {
+    pub running: usize,
+    pub queued: usize,
+}
+
+/// Limits the number of concurrently executed commands.
+///
+/// Commands exceeding the limit are queued and started when one of the running commands
+/// stops. Requests are rejected with an `ErrorKind::Busy` error when the queue is full.
+/// By default, the number of concurrent commands is not limited.
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    inner: Rc<RefCell<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    max_running: Option<usize>,
+    max_queued: Option<usize>,
+    order: QueueOrder,
+    running: usize,
+    seq: u64,
+    queue: Vec<Waiter>,
+}
+
+struct Waiter {
+    priority: i32,
+    seq: u64,
+    tx: oneshot::Sender<Permit>,
+}
+
+impl Scheduler {
+    /// Name of the queue depth state event
+    pub const STATE: &'static str = "command-queue";
+
+    /// Create a scheduler executing up to `max_running` commands at once,
+    /// with an unbounded FIFO queue
+    pub fn new(max_running: usize) -> Self {
+        let scheduler = Self::default();
+        scheduler.inner.borrow_mut().max_running = Some(max_running.max(1));
+        scheduler
+    }
+
+    /// Limit the number of queued commands
+    pub fn queue_limit(self, max_queued: usize) -> Self {
+        self.inner.borrow_mut().max_queued = Some(max_queued);
+        self
+    }
+
+    /// Set the order of queued commands
+    pub fn order(self, order: QueueOrder) -> Self {
+        self.inner.borrow_mut().order = order;
+        self
+    }
+
+    /// Return the number of running and queued commands
+    pub fn depth(&self) -> QueueDepth {
+        let inner = self.inner.borrow();
+        QueueDepth {
+            running: inner.running,
+            queued: inner.queue.len(),
+        }
+    }
+
+    /// Reserve an execution slot, or a place in the queue
+    pub(crate) fn schedule(&self, priority: i32) -> Result<Ticket, Error> {
+        let mut inner = self.inner.borrow_mut();
+        if inner
+            .max_running
+            .map(|max| inner.running < max)
+            .unwrap_or(true)
+        {
+            inner.running += 1;
+            return Ok(Ticket::Ready(Permit::new(self)));
+        }
+        inner.queue.retain(|w| !w.tx.is_canceled());
+        if let Some(max) = inner.max_queued {
+            if inner.queue.len() >= max {
+                return Err(Error::busy("Command queue is full")
+                    .with_context("running", inner.running)
+                    .with_context("queued", inner.queue.len()));
+            }
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let seq = inner.seq;
+        inner.seq += 1;
+        inner.queue.push(Waiter { priority, seq, tx });
+        Ok(Ticket::Queued(rx))
+    }
+
+    /// Hand over the slot of a stopped command to the next queued one
+    fn release(&self) {
+        loop {
+            let waiter = {
+                let mut inner = self.inner.borrow_mut();
+                match inner.next() {
+                    Some(waiter) => waiter,
+                    None => {
+                        inner.running -= 1;
+                        return;
+                    }
+                }
+            };
+            match waiter.tx.send(Permit::new(self)) {
+                Ok(()) => return,
+                // the queued command has been cancelled; try the next one
+                Err(mut permit) => {
+                    permit.scheduler.take();
+                }
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn next(&mut self) -> Option<Waiter> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        let idx = match self.order {
+            QueueOrder::Fifo => 0,
+            QueueOrder::Priority => {
+                let (idx, _) = self
+                    .queue
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, w)| (w.priority, std::cmp::Reverse(w.seq)))?;
+                idx
+            }
+        };
+        Some(self.queue.remove(idx))
+    }
+}
+
+/// Execution slot reservation
+pub(crate) enum Ticket {
+    Ready(Permit),
+    Queued(oneshot::Receiver<Permit>),
+}
+
+impl Ticket {
+    pub fn is_queued(&self) -> bool {
+        matches!(self, Self::Queued(_))
+    }
+
+    /// Wait for an execution slot
+    pub async fn ready(self) -> Option<Permit> {
+        match self {
+            Self::Ready(permit) => Some(permit),
+            Self::Queued(rx) => rx.await.ok(),
+        }
+    }
+}
+
+/// Execution slot, released on drop
+pub(crate) struct Permit {
+    scheduler: Option<Scheduler>,
+}
+
+impl Permit {
+    fn new(scheduler: &Scheduler) -> Self {
+        Self {
+            scheduler: Some(scheduler.clone()),
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.release();
+        }
+    }
+}
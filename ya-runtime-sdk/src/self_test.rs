@@ -0,0 +1,262 @@
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::common::panic_message;
+use crate::error::Error;
+
+/// Result of a self-test check. Errors mark the check as failed
+pub type CheckResult = Result<CheckOutcome, Error>;
+
+/// Successful outcome of a self-test check
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CheckOutcome {
+    /// Check passed, with an optional message
+    Pass(Option<String>),
+    /// Check was not applicable
+    Skip(String),
+}
+
+impl Default for CheckOutcome {
+    fn default() -> Self {
+        Self::Pass(None)
+    }
+}
+
+/// Named self-test check
+pub struct Check {
+    name: String,
+    required: bool,
+    timeout: Option<Duration>,
+    run: LocalBoxFuture<'static, CheckResult>,
+}
+
+impl Check {
+    /// Create a new required check
+    pub fn new<F>(name: impl ToString, check: F) -> Self
+    where
+        F: Future<Output = CheckResult> + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            required: true,
+            timeout: None,
+            run: check.boxed_local(),
+        }
+    }
+
+    /// Do not fail the self-test when this check fails
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    /// Override the self-test timeout for this check
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Check whether `binary` is an executable file, or can be found in `PATH`
+    pub fn binary(binary: impl AsRef<str>) -> Self {
+        let binary = binary.as_ref().to_string();
+        Self::new(format!("binary:{}", binary), async move {
+            let path = find_binary(&binary).ok_or_else(|| {
+                Error::not_found("Binary not found").with_context("binary", &binary)
+            })?;
+            Ok(CheckOutcome::Pass(Some(path.display().to_string())))
+        })
+    }
+
+    /// Check whether files can be created in `dir`
+    pub fn writable(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        Self::new(format!("writable:{}", dir.display()), async move {
+            let path = dir.join(format!(".ya-runtime-sdk-test.{}", std::process::id()));
+            std::fs::write(&path, [])
+                .and_then(|_| std::fs::remove_file(&path))
+                .map_err(|e| Error::from(e).with_context("path", dir.display()))?;
+            Ok(CheckOutcome::default())
+        })
+    }
+
+    /// Check whether `path` exists, e.g. a device node or a `/proc` entry of a kernel feature
+    pub fn exists(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self::new(format!("exists:{}", path.display()), async move {
+            match path.exists() {
+                true => Ok(CheckOutcome::default()),
+                false => {
+                    Err(Error::not_found("Path not found").with_context("path", path.display()))
+                }
+            }
+        })
+    }
+}
+
+/// Set of checks performed by the `test` command.
+///
+/// Checks are executed sequentially, in the order of registration.
+pub struct SelfTest {
+    checks: Vec<Check>,
+    timeout: Duration,
+}
+
+impl Default for SelfTest {
+    fn default() -> Self {
+        Self {
+            checks: Vec::new(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl SelfTest {
+    /// Default timeout of a single check
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a check
+    pub fn check(mut self, check: Check) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    /// Set the default timeout of a single check
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Perform all checks
+    pub async fn run(self) -> SelfTestReport {
+        let mut checks = Vec::with_capacity(self.checks.len());
+
+        for check in self.checks {
+            let timeout = check.timeout.unwrap_or(self.timeout);
+            let started = Instant::now();
+            let result =
+                tokio::time::timeout(timeout, AssertUnwindSafe(check.run).catch_unwind()).await;
+
+            let (status, message) = match result {
+                Ok(Ok(Ok(CheckOutcome::Pass(message)))) => (CheckStatus::Pass, message),
+                Ok(Ok(Ok(CheckOutcome::Skip(message)))) => (CheckStatus::Skip, Some(message)),
+                Ok(Ok(Err(err))) => (CheckStatus::Fail, Some(error_message(&err))),
+                Ok(Err(payload)) => (
+                    CheckStatus::Fail,
+                    Some(format!(
+                        "Check panicked: {}",
+                        panic_message(payload.as_ref())
+                    )),
+                ),
+                Err(_) => (
+                    CheckStatus::Fail,
+                    Some(format!("Timed out after {} ms", timeout.as_millis())),
+                ),
+            };
+
+            #[cfg(feature = "logger")]
+            log::debug!("Self-test check '{}': {:?}", check.name, status);
+
+            checks.push(CheckReport {
+                name: check.name,
+                status,
+                required: check.required,
+                message,
+                duration_ms: started.elapsed().as_millis() as u64,
+            });
+        }
+
+        SelfTestReport {
+            passed: checks
+                .iter()
+                .all(|c| !c.required || c.status != CheckStatus::Fail),
+            checks,
+        }
+    }
+}
+
+/// Status of a performed check
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+/// Report of a performed check
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckReport {
+    pub name: String,
+    pub status: CheckStatus,
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Self-test report, printed by the `test` command
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    /// Whether all required checks have not failed
+    pub passed: bool,
+    pub checks: Vec<CheckReport>,
+}
+
+impl SelfTestReport {
+    /// Return names of failed required checks
+    pub fn failed(&self) -> impl Iterator<Item = &str> {
+        self.checks
+            .iter()
+            .filter(|c| c.required && c.status == CheckStatus::Fail)
+            .map(|c| c.name.as_str())
+    }
+}
+
+fn error_message(err: &Error) -> String {
+    let mut context = err
+        .context()
+        .iter()
+        .map(|(k, v)| format!("{}: {}", k, v))
+        .collect::<Vec<_>>();
+    match context.is_empty() {
+        true => err.message().to_string(),
+        false => {
+            context.sort();
+            format!("{} ({})", err.message(), context.join(", "))
+        }
+    }
+}
+
+fn find_binary(binary: &str) -> Option<PathBuf> {
+    if binary.contains(std::path::MAIN_SEPARATOR) {
+        let path = PathBuf::from(binary);
+        return is_executable(&path).then_some(path);
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(binary))
+        .find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+    #[cfg(not(unix))]
+    path.is_file()
+}
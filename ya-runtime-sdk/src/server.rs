@@ -1,22 +1,33 @@
 use std::cell::RefCell;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
 use std::rc::Rc;
+use std::time::Duration;
 
 use futures::channel::oneshot;
 use futures::{FutureExt, TryFutureExt};
+use tokio::task::JoinHandle;
 use ya_runtime_api::server::proto::response::create_network::Endpoint;
 use ya_runtime_api::server::{
-    AsyncResponse, CreateNetwork, CreateNetworkResp, KillProcess, RunProcess, RunProcessResp,
-    RuntimeService,
+    AsyncResponse, CreateNetwork, CreateNetworkResp, ErrorResponse, KillProcess, RunProcess,
+    RunProcessResp, RuntimeService, RuntimeState,
 };
 
 pub use ya_runtime_api::deploy::ContainerEndpoint;
 
+use crate::common::panic_message;
+use crate::error::Error;
+use crate::event::EventEmitter;
+use crate::recording::{Recorder, Request, Response};
 use crate::runtime::RuntimeMode;
+use crate::serialize::json;
+use crate::trace;
 use crate::{Context, Runtime, RuntimeDef};
 
 pub struct Server<R: Runtime> {
     pub(crate) runtime: Rc<RefCell<R>>,
     pub(crate) ctx: Rc<RefCell<Context<R>>>,
+    checkpoints: Rc<RefCell<Option<JoinHandle<()>>>>,
 }
 
 impl<R: Runtime + 'static> Server<R> {
@@ -27,9 +38,14 @@ impl<R: Runtime + 'static> Server<R> {
         let server = Self {
             runtime: Rc::new(RefCell::new(runtime)),
             ctx: Rc::new(RefCell::new(ctx)),
+            checkpoints: Default::default(),
         };
 
         server.shutdown_on(rx);
+        let checkpoints = server.ctx.borrow().checkpoints();
+        if let Some(checkpoints) = checkpoints {
+            server.checkpoint_every(checkpoints.interval);
+        }
         server
     }
 
@@ -42,6 +58,83 @@ impl<R: Runtime + 'static> Server<R> {
             }
         }));
     }
+
+    /// Save checkpoints periodically, until the runtime is shut down
+    fn checkpoint_every(&self, interval: Duration) {
+        let server = self.clone();
+        let handle = tokio::task::spawn_local(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                server.checkpoint();
+            }
+        });
+        self.checkpoints.borrow_mut().replace(handle);
+    }
+}
+
+impl<R: Runtime> Server<R> {
+    /// Save a runtime checkpoint, unless the runtime is currently in use
+    fn checkpoint(&self) {
+        let (runtime, ctx) = match (self.runtime.try_borrow(), self.ctx.try_borrow()) {
+            (Ok(runtime), Ok(ctx)) => (runtime, ctx),
+            _ => return,
+        };
+        if let Some(checkpoints) = ctx.checkpoints() {
+            if let Err(_e) = (checkpoints.save)(&ctx, &runtime) {
+                #[cfg(feature = "logger")]
+                log::warn!("Unable to save a checkpoint: {}", _e);
+            }
+        }
+    }
+
+    fn recorder(&self) -> Option<Recorder> {
+        self.ctx.borrow().recorder.clone()
+    }
+
+    /// Terminate the runtime when `f` or the future it returns panics.
+    /// Emits a RuntimeState "error" event beforehand, if enabled in `Context`
+    fn guard<'a, T: 'a, F>(&'a self, f: impl FnOnce() -> F) -> AsyncResponse<'a, T>
+    where
+        F: Future<Output = Result<T, ErrorResponse>> + 'a,
+    {
+        let fut = std::panic::catch_unwind(AssertUnwindSafe(f));
+        async move {
+            let payload = match fut {
+                Ok(fut) => match AssertUnwindSafe(fut).catch_unwind().await {
+                    Ok(result) => return result,
+                    Err(payload) => payload,
+                },
+                Err(payload) => payload,
+            };
+
+            let message = panic_message(payload.as_ref());
+            let (emitter, control) = {
+                let ctx = self.ctx.borrow();
+                let emitter = ctx.emitter.clone().filter(|_| ctx.report_panics);
+                (emitter, ctx.control.clone())
+            };
+            if let Some(emitter) = emitter {
+                report_error(emitter, &message).await;
+            }
+
+            let error = Error::internal(format!("Runtime panic: {}", message));
+            control.fail(error.clone());
+            Err(error.into())
+        }
+        .boxed_local()
+    }
+}
+
+/// Emits a RuntimeState "error" event and waits for it to reach the supervisor
+pub(crate) async fn report_error(mut emitter: EventEmitter, message: &str) {
+    let value = json::json!({ "message": message }).to_string();
+    emitter
+        .state(RuntimeState {
+            name: "error".to_string(),
+            value: value.into_bytes(),
+        })
+        .await;
+    emitter.flush().await;
 }
 
 impl<R: Runtime> Clone for Server<R> {
@@ -49,67 +142,126 @@ impl<R: Runtime> Clone for Server<R> {
         Self {
             runtime: self.runtime.clone(),
             ctx: self.ctx.clone(),
+            checkpoints: self.checkpoints.clone(),
         }
     }
 }
 
 impl<R: Runtime> RuntimeService for Server<R> {
-    fn hello(&self, _version: &str) -> AsyncResponse<'_, String> {
-        async { Ok(<R as RuntimeDef>::VERSION.to_owned()) }.boxed_local()
+    fn hello(&self, version: &str) -> AsyncResponse<'_, String> {
+        self.guard(|| {
+            let fut = async { Ok(<R as RuntimeDef>::VERSION.to_owned()) };
+            let fut = Recorder::call(
+                self.recorder(),
+                || Request::Hello {
+                    version: version.to_string(),
+                },
+                || fut,
+                |version| Response::Hello {
+                    version: version.clone(),
+                },
+            );
+            trace::service("hello", fut)
+        })
     }
 
     fn run_process(&self, run: RunProcess) -> AsyncResponse<'_, RunProcessResp> {
-        let mut runtime = self.runtime.borrow_mut();
-        let mut ctx = self.ctx.borrow_mut();
-        runtime
-            .run_command(run, RuntimeMode::Server, &mut ctx)
-            .then(|result| async move {
-                match result {
-                    Ok(pid) => Ok(RunProcessResp { pid }),
-                    Err(err) => Err(err.into()),
-                }
-            })
-            .boxed_local()
+        self.guard(|| {
+            let mut runtime = self.runtime.borrow_mut();
+            let mut ctx = self.ctx.borrow_mut();
+            let command = run.bin.clone();
+            let request = Request::from(&run);
+            let recorder = ctx.recorder.clone();
+            let fut = || {
+                runtime
+                    .run_command(run, RuntimeMode::Server, &mut ctx)
+                    .then(|result| async move {
+                        match result {
+                            Ok(pid) => Ok(RunProcessResp { pid }),
+                            Err(err) => Err(err.into()),
+                        }
+                    })
+            };
+            let fut = Recorder::call(
+                recorder,
+                || request,
+                fut,
+                |resp| Response::RunProcess { pid: resp.pid },
+            );
+            trace::service("run_process", trace::run_process(&command, fut))
+        })
     }
 
     fn kill_process(&self, kill: KillProcess) -> AsyncResponse<'_, ()> {
-        let mut runtime = self.runtime.borrow_mut();
-        let mut ctx = self.ctx.borrow_mut();
-        runtime
-            .kill_command(kill, &mut ctx)
-            .map_err(Into::into)
-            .boxed_local()
+        self.guard(|| {
+            let mut runtime = self.runtime.borrow_mut();
+            let mut ctx = self.ctx.borrow_mut();
+            let request = Request::from(&kill);
+            let recorder = ctx.recorder.clone();
+            let fut = || runtime.kill_command(kill, &mut ctx).map_err(Into::into);
+            let fut = Recorder::call(recorder, || request, fut, |_| Response::KillProcess);
+            trace::service("kill_process", fut)
+        })
     }
 
     fn create_network(&self, network: CreateNetwork) -> AsyncResponse<'_, CreateNetworkResp> {
-        let mut runtime = self.runtime.borrow_mut();
-        let mut ctx = self.ctx.borrow_mut();
-        runtime
-            .join_network(network, &mut ctx)
-            .map(|result| {
-                result.map(|e| CreateNetworkResp {
-                    endpoint: match &e {
-                        ContainerEndpoint::UnixStream(_) => {
-                            Some(Endpoint::UnixStream(e.to_string()))
-                        }
-                        ContainerEndpoint::UnixDatagram(_) => {
-                            Some(Endpoint::UnixDatagram(e.to_string()))
-                        }
-                        ContainerEndpoint::UdpDatagram(_) => {
-                            Some(Endpoint::UdpDatagram(e.to_string()))
-                        }
-                        ContainerEndpoint::TcpStream(_) => Some(Endpoint::TcpStream(e.to_string())),
-                        _ => None,
-                    },
-                })
-            })
-            .map_err(Into::into)
-            .boxed_local()
+        self.guard(|| {
+            let mut runtime = self.runtime.borrow_mut();
+            let mut ctx = self.ctx.borrow_mut();
+            let request = Request::from(&network);
+            let recorder = ctx.recorder.clone();
+            let fut = || {
+                runtime
+                    .join_network(network, &mut ctx)
+                    .map(|result| {
+                        result.map(|e| CreateNetworkResp {
+                            endpoint: match &e {
+                                ContainerEndpoint::UnixStream(_) => {
+                                    Some(Endpoint::UnixStream(e.to_string()))
+                                }
+                                ContainerEndpoint::UnixDatagram(_) => {
+                                    Some(Endpoint::UnixDatagram(e.to_string()))
+                                }
+                                ContainerEndpoint::UdpDatagram(_) => {
+                                    Some(Endpoint::UdpDatagram(e.to_string()))
+                                }
+                                ContainerEndpoint::TcpStream(_) => {
+                                    Some(Endpoint::TcpStream(e.to_string()))
+                                }
+                                _ => None,
+                            },
+                        })
+                    })
+                    .map_err(Into::into)
+            };
+            let fut = Recorder::call(recorder, || request, fut, |resp| Response::from(resp));
+            trace::service("create_network", fut)
+        })
     }
 
     fn shutdown(&self) -> AsyncResponse<'_, ()> {
-        let mut runtime = self.runtime.borrow_mut();
-        let mut ctx = self.ctx.borrow_mut();
-        runtime.stop(&mut ctx).map_err(Into::into).boxed_local()
+        if let Some(handle) = self.checkpoints.borrow_mut().take() {
+            handle.abort();
+        }
+
+        self.guard(|| {
+            let mut runtime = self.runtime.borrow_mut();
+            let mut ctx = self.ctx.borrow_mut();
+            let server = self.clone();
+            let recorder = ctx.recorder.clone();
+            let fut = || {
+                runtime
+                    .stop(&mut ctx)
+                    .map(move |result| {
+                        if result.is_ok() {
+                            server.checkpoint();
+                        }
+                        result
+                    })
+                    .map_err(Into::into)
+            };
+            let fut = Recorder::call(recorder, || Request::Shutdown, fut, |_| Response::Shutdown);
+            trace::service("shutdown", fut)
+        })
     }
 }
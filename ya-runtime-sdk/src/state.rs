@@ -0,0 +1,175 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+/// Persistent key-value store, rooted in the runtime working directory.
+///
+/// Each value is stored as a separate JSON file, replaced atomically on write.
+/// Values written in one invocation of the runtime (e.g. `deploy`) are
+/// available to the following ones (e.g. `start` or `run`).
+#[derive(Clone, Debug)]
+pub struct StateStore {
+    dir: PathBuf,
+}
+
+impl StateStore {
+    /// Name of the state directory, relative to the working directory
+    pub const DIRECTORY: &'static str = ".state";
+    const EXTENSION: &'static str = "json";
+    const LOCK_EXTENSION: &'static str = "lock";
+
+    /// Create a store within the specified working directory
+    pub fn new<P: AsRef<Path>>(workdir: P) -> Self {
+        Self {
+            dir: workdir.as_ref().join(Self::DIRECTORY),
+        }
+    }
+
+    /// Return the state directory path
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Read a value stored under `key`
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Error> {
+        let path = self.key_path(key)?;
+        let contents = match std::fs::read(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::from(e).with_context("key", key)),
+        };
+        serde_json::from_slice(&contents).map(Some).map_err(|e| {
+            Error::internal(format!("Invalid state value: {}", e)).with_context("key", key)
+        })
+    }
+
+    /// Store `value` under `key`, replacing the previous value
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> Result<(), Error> {
+        let path = self.key_path(key)?;
+        let contents = serde_json::to_vec_pretty(value)
+            .map_err(|e| Error::invalid_argument(format!("Invalid state value: {}", e)))
+            .map_err(|e| e.with_context("key", key))?;
+        write_atomic(&path, &contents).map_err(|e| Error::from(e).with_context("key", key))
+    }
+
+    /// Read, modify and store the value under `key`
+    pub fn update<T, F>(&self, key: &str, f: F) -> Result<T, Error>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(Option<T>) -> T,
+    {
+        let value = f(self.get(key)?);
+        self.set(key, &value)?;
+        Ok(value)
+    }
+
+    /// Take an exclusive advisory lock on `key`, held until the returned guard is dropped.
+    /// Concurrent lock attempts, also by other processes, wait until then
+    pub fn lock(&self, key: &str) -> Result<StateLock, Error> {
+        let path = self.key_path(key)?.with_extension(Self::LOCK_EXTENSION);
+        std::fs::create_dir_all(&self.dir)?;
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&path)
+            .map_err(|e| Error::from(e).with_context("key", key))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+                let err = io::Error::last_os_error();
+                return Err(Error::from(err).with_context("key", key));
+            }
+        }
+        Ok(StateLock { _file: file })
+    }
+
+    /// Remove the value stored under `key`. Returns whether the value existed
+    pub fn remove(&self, key: &str) -> Result<bool, Error> {
+        let path = self.key_path(key)?;
+        match std::fs::remove_file(path) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(Error::from(e).with_context("key", key)),
+        }
+    }
+
+    /// Check whether a value is stored under `key`
+    pub fn contains(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.key_path(key)?.is_file())
+    }
+
+    /// List stored keys
+    pub fn keys(&self) -> Result<Vec<String>, Error> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut keys = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(Self::EXTENSION) {
+                continue;
+            }
+            if let Some(key) = path.file_stem().and_then(|s| s.to_str()) {
+                if validate_key(key).is_ok() {
+                    keys.push(key.to_string());
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn key_path(&self, key: &str) -> Result<PathBuf, Error> {
+        validate_key(key)?;
+        Ok(self.dir.join(format!("{}.{}", key, Self::EXTENSION)))
+    }
+}
+
+/// Exclusive lock on a `StateStore` key, released on drop
+pub struct StateLock {
+    _file: std::fs::File,
+}
+
+/// Keys are non-empty strings of ASCII alphanumerics, '-', '_' and '.',
+/// not starting with a '.'
+fn validate_key(key: &str) -> Result<(), Error> {
+    let valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    match valid {
+        true => Ok(()),
+        false => Err(Error::invalid_argument("Invalid state key").with_context("key", key)),
+    }
+}
+
+/// Write to a temporary file and move it in place of the destination
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid path"))?;
+    std::fs::create_dir_all(dir)?;
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = dir.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
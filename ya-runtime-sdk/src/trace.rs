@@ -0,0 +1,70 @@
+use std::future::Future;
+
+#[cfg(feature = "tracing")]
+use tracing::Instrument;
+
+use crate::runtime::ProcessId;
+
+/// Instrument a runtime execution phase
+#[cfg(feature = "tracing")]
+pub(crate) fn phase<F: Future>(phase: &'static str, fut: F) -> impl Future<Output = F::Output> {
+    fut.instrument(tracing::info_span!("phase", phase))
+}
+
+/// Instrument a Runtime API service call
+#[cfg(feature = "tracing")]
+pub(crate) fn service<F: Future>(call: &'static str, fut: F) -> impl Future<Output = F::Output> {
+    fut.instrument(tracing::info_span!("service", call))
+}
+
+/// Instrument a command execution request
+#[cfg(feature = "tracing")]
+pub(crate) fn run_process<F: Future>(command: &str, fut: F) -> impl Future<Output = F::Output> {
+    fut.instrument(tracing::info_span!("run_process", command))
+}
+
+/// Instrument a command lifecycle. `command` is empty for handlers started without a request
+#[cfg(feature = "tracing")]
+pub(crate) fn command<F: Future>(
+    pid: ProcessId,
+    command: Option<&str>,
+    fut: F,
+) -> impl Future<Output = F::Output> {
+    let command = command.unwrap_or_default();
+    fut.instrument(tracing::info_span!("command", pid, command))
+}
+
+/// Instrument a future with the current span
+#[cfg(feature = "tracing")]
+pub(crate) fn current<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    fut.in_current_span()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn phase<F: Future>(_: &'static str, fut: F) -> impl Future<Output = F::Output> {
+    fut
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn service<F: Future>(_: &'static str, fut: F) -> impl Future<Output = F::Output> {
+    fut
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn run_process<F: Future>(_: &str, fut: F) -> impl Future<Output = F::Output> {
+    fut
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn command<F: Future>(
+    _: ProcessId,
+    _: Option<&str>,
+    fut: F,
+) -> impl Future<Output = F::Output> {
+    fut
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn current<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    fut
+}
@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::Error;
+use crate::state::StateStore;
+
+/// Volume mapping a directory in the working directory to a path inside the runtime
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Volume {
+    /// Directory name, relative to the working directory
+    pub name: String,
+    /// Path seen from inside the runtime, e.g. `/in`
+    pub path: String,
+}
+
+/// Volumes declared by the runtime.
+///
+/// Volume directories are created in the working directory on `deploy`
+/// and included in the `vols` property of the deployment output.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Volumes {
+    vols: Vec<Volume>,
+}
+
+impl Volumes {
+    const KEY: &'static str = "ya-runtime-sdk.volumes";
+
+    /// Declare a volume mounted at `path`, with a directory name derived from the path
+    pub fn add(&mut self, path: impl AsRef<str>) -> Result<&Volume, Error> {
+        let path = path.as_ref();
+        let name = normalize(path)?
+            .iter()
+            .map(|c| {
+                c.chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("-");
+        self.add_named(format!("vol-{}", name), path)
+    }
+
+    /// Declare a volume mounted at `path`, stored in the `name` directory
+    pub fn add_named(
+        &mut self,
+        name: impl ToString,
+        path: impl AsRef<str>,
+    ) -> Result<&Volume, Error> {
+        let name = name.to_string();
+        let components = normalize(path.as_ref())?;
+        if components.is_empty() {
+            return Err(
+                Error::invalid_argument("Invalid volume path").with_context("path", path.as_ref())
+            );
+        }
+        let path = format!("/{}", components.join("/"));
+
+        let valid_name = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_name {
+            return Err(Error::invalid_argument("Invalid volume name").with_context("name", name));
+        }
+
+        let volume = Volume { name, path };
+        let idx = match self.vols.iter().position(|v| *v == volume) {
+            Some(idx) => idx,
+            None => {
+                if let Some(v) = self
+                    .vols
+                    .iter()
+                    .find(|v| v.name == volume.name || v.path == volume.path)
+                {
+                    return Err(Error::invalid_argument("Conflicting volume declaration")
+                        .with_context("name", &v.name)
+                        .with_context("path", &v.path));
+                }
+                self.vols.push(volume);
+                self.vols.len() - 1
+            }
+        };
+        Ok(&self.vols[idx])
+    }
+
+    /// Return declared volumes
+    pub fn iter(&self) -> impl Iterator<Item = &Volume> {
+        self.vols.iter()
+    }
+
+    /// Check whether any volumes were declared
+    pub fn is_empty(&self) -> bool {
+        self.vols.is_empty()
+    }
+
+    /// Resolve a path inside the runtime, e.g. `/in/file`, to a path in the working directory.
+    /// Paths outside of the declared volumes, or escaping them, are rejected
+    pub fn resolve(
+        &self,
+        workdir: impl AsRef<Path>,
+        path: impl AsRef<str>,
+    ) -> Result<PathBuf, Error> {
+        let path = path.as_ref();
+        let components = normalize(path)?;
+
+        let (volume, depth) = self
+            .vols
+            .iter()
+            .filter_map(|v| {
+                let prefix = normalize(&v.path).ok()?;
+                components.starts_with(&prefix).then_some((v, prefix.len()))
+            })
+            .max_by_key(|(_, depth)| *depth)
+            .ok_or_else(|| {
+                Error::not_found("Path is not within a volume").with_context("path", path)
+            })?;
+
+        let volume_dir = workdir.as_ref().join(&volume.name);
+        let resolved = components[depth..]
+            .iter()
+            .fold(volume_dir.clone(), |acc, c| acc.join(c));
+
+        // reject symbolic links pointing outside of the volume, including paths yet to be created
+        let escapes = match (canonicalize(&resolved), canonicalize(&volume_dir)) {
+            (Some(resolved), Some(volume_dir)) => !resolved.starts_with(volume_dir),
+            _ => true,
+        };
+        if escapes {
+            return Err(
+                Error::invalid_argument("Path escapes the volume").with_context("path", path)
+            );
+        }
+        Ok(resolved)
+    }
+
+    /// Create volume directories in the working directory
+    pub fn create(&self, workdir: impl AsRef<Path>) -> Result<(), Error> {
+        for volume in self.vols.iter() {
+            std::fs::create_dir_all(workdir.as_ref().join(&volume.name))?;
+        }
+        Ok(())
+    }
+
+    /// Read volumes declared in a previous invocation of the runtime.
+    /// Unreadable state is reported and replaced with no volumes
+    pub(crate) fn load(store: &StateStore) -> Self {
+        match store.get(Self::KEY) {
+            Ok(volumes) => volumes.unwrap_or_default(),
+            Err(_e) => {
+                #[cfg(feature = "logger")]
+                log::warn!("Unable to read the declared volumes: {}", _e);
+                Default::default()
+            }
+        }
+    }
+
+    pub(crate) fn save(&self, store: &StateStore) -> Result<(), Error> {
+        store.set(Self::KEY, self)
+    }
+}
+
+/// Canonicalize the longest existing ancestor of `path` and re-append the remaining components.
+/// Returns `None` for dangling symbolic links and unreadable paths
+fn canonicalize(path: &Path) -> Option<PathBuf> {
+    let mut missing = Vec::new();
+    let mut ancestor = path;
+    loop {
+        match ancestor.canonicalize() {
+            Ok(canonical) => {
+                return Some(missing.iter().rev().fold(canonical, |acc, c| acc.join(c)));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                // a dangling symbolic link may point anywhere
+                if ancestor.symlink_metadata().is_ok() {
+                    return None;
+                }
+                missing.push(ancestor.file_name()?);
+                ancestor = match ancestor.parent()? {
+                    parent if parent.as_os_str().is_empty() => Path::new("."),
+                    parent => parent,
+                };
+            }
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Split an absolute path into its components. `..` may not escape the root directory
+fn normalize(path: &str) -> Result<Vec<String>, Error> {
+    let invalid = || Error::invalid_argument("Invalid path").with_context("path", path);
+    if !path.starts_with('/') {
+        return Err(invalid());
+    }
+
+    let mut components = Vec::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::RootDir | Component::CurDir => (),
+            Component::Normal(c) => components.push(c.to_str().ok_or_else(invalid)?.to_string()),
+            Component::ParentDir => {
+                components.pop().ok_or_else(invalid)?;
+            }
+            Component::Prefix(_) => return Err(invalid()),
+        }
+    }
+    Ok(components)
+}
@@ -0,0 +1,140 @@
+mod utils;
+
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use utils::{settle, Env};
+use ya_runtime_sdk::runtime_api::server::RuntimeService;
+use ya_runtime_sdk::serialize::json;
+use ya_runtime_sdk::server::Server;
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct CounterRuntime {
+    counter: u64,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Snapshot {
+    counter: u64,
+}
+
+impl_empty_runtime!(CounterRuntime, {
+    fn stop<'a>(&mut self, _: &mut Context<Self>) -> EmptyResponse<'a> {
+        self.counter += 1;
+        async move { Ok(()) }.boxed_local()
+    }
+
+    fn run_command<'a>(
+        &mut self,
+        _: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        ctx.command(|_| async move { Ok(()) })
+    }
+});
+
+impl Checkpoint for CounterRuntime {
+    const INTERVAL: Duration = Duration::from_millis(10);
+    type Snapshot = Snapshot;
+
+    fn snapshot(&self) -> Self::Snapshot {
+        Snapshot {
+            counter: self.counter,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Self::Snapshot) -> Result<(), Error> {
+        self.counter = snapshot.counter;
+        Ok(())
+    }
+}
+
+fn context(dir: &tempdir::TempDir) -> Context<CounterRuntime> {
+    let env = Env::new(dir.path(), &["start"]).workdir();
+    let mut ctx = Context::<CounterRuntime>::try_with(env).unwrap();
+    ctx.enable_checkpoints();
+    ctx
+}
+
+#[tokio::test]
+async fn checkpoint_restore() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let mut ctx = context(&temp_dir);
+            let mut runtime = CounterRuntime::default();
+            assert!(!ctx.restore_checkpoint(&mut runtime).unwrap());
+
+            let mut pid = 0;
+            for _ in 0..3 {
+                let command = RunProcess::default();
+                pid = runtime
+                    .run_command(command, RuntimeMode::Server, &mut ctx)
+                    .await
+                    .unwrap();
+            }
+            runtime.counter = 41;
+
+            let server = Server::new(runtime, ctx);
+            server.shutdown().await.unwrap();
+
+            let mut ctx = context(&temp_dir);
+            let mut runtime = CounterRuntime::default();
+            assert!(ctx.restore_checkpoint(&mut runtime).unwrap());
+            assert_eq!(runtime.counter, 42);
+
+            let command = RunProcess::default();
+            let next = runtime
+                .run_command(command, RuntimeMode::Server, &mut ctx)
+                .await
+                .unwrap();
+            assert_eq!(next, pid + 1);
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn checkpoint_periodic() {
+    tokio::time::pause();
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let runtime = CounterRuntime { counter: 7 };
+            let server = Server::new(runtime, context(&temp_dir));
+            settle().await;
+            tokio::time::advance(CounterRuntime::INTERVAL).await;
+            settle().await;
+
+            let mut runtime = CounterRuntime::default();
+            assert!(context(&temp_dir).restore_checkpoint(&mut runtime).unwrap());
+            assert_eq!(runtime.counter, 7);
+
+            // no periodic checkpoints after shutdown
+            server.shutdown().await.unwrap();
+            let store = context(&temp_dir).state_store().unwrap();
+            assert!(store.remove("ya-runtime-sdk.checkpoint").unwrap());
+            tokio::time::advance(CounterRuntime::INTERVAL * 3).await;
+            settle().await;
+
+            let mut runtime = CounterRuntime::default();
+            assert!(!context(&temp_dir).restore_checkpoint(&mut runtime).unwrap());
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn checkpoint_invalid() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let mut ctx = context(&temp_dir);
+    let snapshot = json::json!({ "pidSeq": 3, "runtime": "invalid" });
+    let store = ctx.state_store().unwrap();
+    store.set("ya-runtime-sdk.checkpoint", &snapshot).unwrap();
+
+    let mut runtime = CounterRuntime { counter: 5 };
+    assert!(ctx.restore_checkpoint(&mut runtime).is_err());
+    assert_eq!(runtime.counter, 5);
+}
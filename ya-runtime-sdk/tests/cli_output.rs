@@ -0,0 +1,191 @@
+mod utils;
+
+use futures::FutureExt;
+use utils::Env;
+use ya_runtime_sdk::serialize::json::json;
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+#[runtime(mode = "command")]
+struct CommandRuntime;
+
+impl Runtime for CommandRuntime {
+    fn deploy<'a>(&mut self, _: &mut Context<Self>) -> OutputResponse<'a> {
+        async move { Ok(Some(json!({"startMode": "empty"}))) }.boxed_local()
+    }
+
+    fn start<'a>(&mut self, _: &mut Context<Self>) -> OutputResponse<'a> {
+        async move { Ok(Some(json!({"started": true}))) }.boxed_local()
+    }
+
+    fn run_command<'a>(
+        &mut self,
+        command: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        ctx.command(move |mut run_ctx| async move {
+            match command.bin.as_str() {
+                "fail" => Err(Error::internal("failure")),
+                "missing" => Err(Error::not_found("missing").with_context("bin", "missing")),
+                "print" => {
+                    run_ctx.stdout("raw\n").await;
+                    Ok(serialize::json::Value::Null)
+                }
+                "panic" => panic!("handler failure"),
+                _ => Ok(json!(command.args)),
+            }
+        })
+    }
+}
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+#[runtime(mode = "command")]
+struct FailingRuntime;
+
+impl Runtime for FailingRuntime {
+    fn deploy<'a>(&mut self, _: &mut Context<Self>) -> OutputResponse<'a> {
+        let err = Error::not_found("Image not found").with_context("image", "missing");
+        async move { Err(err) }.boxed_local()
+    }
+
+    fn start<'a>(&mut self, _: &mut Context<Self>) -> OutputResponse<'a> {
+        unimplemented!()
+    }
+
+    fn run_command<'a>(
+        &mut self,
+        _: RunProcess,
+        _: RuntimeMode,
+        _: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        unimplemented!()
+    }
+}
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct ServerRuntime;
+
+impl_empty_runtime!(ServerRuntime, {
+    fn run_command<'a>(
+        &mut self,
+        _: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        ctx.command(|_| async move { Ok(()) })
+    }
+});
+
+async fn invoke<R>(args: &[&str]) -> (anyhow::Result<()>, String)
+where
+    R: Runtime + Default + 'static,
+{
+    let env = Env::temp(args).workdir();
+    let result = ya_runtime_sdk::build(env.clone(), |_| async move { Ok(R::default()) }).await;
+    (result, env.output())
+}
+
+#[tokio::test]
+async fn command_mode_output() {
+    let (result, output) = invoke::<CommandRuntime>(&["deploy"]).await;
+    assert!(result.is_ok());
+    assert_eq!(output, "{\"startMode\":\"empty\",\"vols\":[]}\n");
+
+    let (result, output) = invoke::<CommandRuntime>(&["start"]).await;
+    assert!(result.is_ok());
+    assert_eq!(output, "{\"started\":true}\n");
+
+    let (result, output) = invoke::<CommandRuntime>(&["run", "echo", "a", "b"]).await;
+    assert!(result.is_ok());
+    assert_eq!(output, "[\"a\",\"b\"]\n");
+
+    // command output is printed to stderr
+    let (result, output) = invoke::<CommandRuntime>(&["run", "print"]).await;
+    assert!(result.is_ok());
+    assert_eq!(output, "");
+
+    let (result, output) = invoke::<CommandRuntime>(&["offer-template"]).await;
+    assert!(result.is_ok());
+    assert_eq!(output, "{\"constraints\":\"\",\"properties\":{}}\n");
+
+    let (result, output) = invoke::<CommandRuntime>(&["plugin-descriptor"]).await;
+    assert!(result.is_ok());
+    let descriptor = PluginDescriptor::new(
+        <CommandRuntime as RuntimeDef>::NAME,
+        <CommandRuntime as RuntimeDef>::VERSION,
+        std::env::current_exe().unwrap(),
+    );
+    let expected = serialize::json::to_string(&[descriptor]).unwrap();
+    assert_eq!(output, format!("{}\n", expected));
+
+    let (result, output) = invoke::<CommandRuntime>(&["test"]).await;
+    assert!(result.is_ok());
+    assert!(output.starts_with("{\"passed\":true,\"checks\":[{\"name\":\"test\""));
+    assert_eq!(output.lines().count(), 1);
+    assert!(output.ends_with("}]}\n"));
+}
+
+#[tokio::test]
+async fn command_mode_errors() {
+    let (result, output) = invoke::<CommandRuntime>(&["run", "fail"]).await;
+    assert_eq!(exit_code(&result.unwrap_err()), 1);
+    assert_eq!(
+        output,
+        "{\"code\":1,\"message\":\"failure\",\"context\":{}}\n"
+    );
+
+    // code and context of errors returned by handlers are retained
+    let (result, output) = invoke::<CommandRuntime>(&["run", "missing"]).await;
+    assert_eq!(exit_code(&result.unwrap_err()), 1);
+    assert_eq!(
+        output,
+        "{\"code\":2,\"message\":\"missing\",\"context\":{\"bin\":\"missing\"}}\n"
+    );
+
+    let (result, output) = invoke::<FailingRuntime>(&["deploy"]).await;
+    assert_eq!(exit_code(&result.unwrap_err()), ErrorKind::NotFound.code());
+    assert_eq!(
+        output,
+        "{\"code\":2,\"message\":\"Image not found\",\"context\":{\"image\":\"missing\"}}\n"
+    );
+
+    let (result, output) = invoke::<CommandRuntime>(&["run", "panic"]).await;
+    assert_eq!(exit_code(&result.unwrap_err()), RETURN_CODE_PANIC);
+    let error: serialize::json::Value = serialize::json::from_str(&output).unwrap();
+    assert_eq!(error["code"], ErrorKind::Internal.code());
+    assert_eq!(error["message"], "Command panicked: handler failure");
+
+    let (result, output) = invoke::<CommandRuntime>(&["unknown-command"]).await;
+    assert_eq!(exit_code(&result.unwrap_err()), 1);
+    assert!(output.starts_with("{\"code\":1,\"message\":"));
+    assert_eq!(output.lines().count(), 1);
+
+    let (result, output) = invoke::<CommandRuntime>(&["run"]).await;
+    assert!(result.is_err());
+    assert_eq!(
+        output,
+        "{\"code\":1,\"message\":\"not enough arguments\",\"context\":{}}\n"
+    );
+}
+
+#[tokio::test]
+async fn server_mode_run_output() {
+    let (result, output) = invoke::<ServerRuntime>(&["run", "echo"]).await;
+    assert!(result.is_ok());
+    assert_eq!(output, "0\n");
+}
+
+#[tokio::test]
+async fn pretty_output() {
+    let (result, output) = invoke::<CommandRuntime>(&["--pretty", "run", "echo", "a"]).await;
+    assert!(result.is_ok());
+    assert_eq!(output, "[\n  \"a\"\n]\n");
+
+    let (result, output) = invoke::<CommandRuntime>(&["--pretty", "run", "fail"]).await;
+    assert!(result.is_err());
+    assert_eq!(
+        output,
+        "{\n  \"code\": 1,\n  \"message\": \"failure\",\n  \"context\": {}\n}\n"
+    );
+}
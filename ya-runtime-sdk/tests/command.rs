@@ -0,0 +1,82 @@
+mod utils;
+
+use utils::{Env, Events};
+use ya_runtime_sdk::runtime_api::server::RuntimeService;
+use ya_runtime_sdk::server::Server;
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct Runtime;
+impl_empty_runtime!(Runtime);
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct PanicRuntime;
+
+impl_empty_runtime!(PanicRuntime, {
+    fn stop<'a>(&mut self, _: &mut Context<Self>) -> EmptyResponse<'a> {
+        unimplemented!()
+    }
+
+    fn run_command<'a>(
+        &mut self,
+        _: RunProcess,
+        _: RuntimeMode,
+        _: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        panic!("runtime failure");
+    }
+});
+
+#[tokio::test]
+async fn command_panic() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let env = Env::temp(&["run"]).workdir();
+            let mut ctx = Context::<Runtime>::try_with(env).unwrap();
+            ctx.emitter = Some(events.emitter());
+
+            let pid = ctx
+                .command(|_| async move {
+                    if true {
+                        panic!("handler failure");
+                    }
+                    Ok(())
+                })
+                .await
+                .unwrap();
+
+            let stopped = events.stopped(pid).await;
+            assert_eq!(stopped.return_code, RETURN_CODE_PANIC);
+            assert!(events.stderr(pid).concat().contains("handler failure"));
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn runtime_panic() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let mut ctx = Context::<PanicRuntime>::try_with(Env::temp(&["start"])).unwrap();
+            ctx.emitter = Some(events.emitter());
+            ctx.report_panics = true;
+
+            let server = Server::new(PanicRuntime, ctx);
+            let err = server.run_process(Default::default()).await.unwrap_err();
+            assert_eq!(err.code, error::ErrorKind::Internal.code());
+            assert!(err.message.contains("runtime failure"));
+
+            // the event is delivered before the call fails
+            let runtime = events.runtime.lock().unwrap();
+            let state = runtime.iter().find_map(|status| match &status.kind {
+                Some(RuntimeStatusKind::State(state)) if state.name == "error" => Some(state),
+                _ => None,
+            });
+            let value = String::from_utf8(state.unwrap().value.clone()).unwrap();
+            assert!(value.contains("runtime failure"));
+        })
+        .await;
+}
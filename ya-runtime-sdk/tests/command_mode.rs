@@ -0,0 +1,146 @@
+mod utils;
+
+use std::path::Path;
+use utils::Env;
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+#[runtime(mode = "command")]
+struct CommandRuntime;
+
+impl_empty_runtime!(CommandRuntime, {
+    fn run_command<'a>(
+        &mut self,
+        command: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        ctx.command(move |_| async move {
+            match command.bin.as_str() {
+                "fail" => Err(Error::internal("failure")),
+                "echo" => Ok(serialize::json::json!("output")),
+                _ => Ok(serialize::json::Value::Null),
+            }
+        })
+    }
+});
+
+/// Invoke the `run` command via command line
+async fn invoke(dir: &Path, bin: &str) -> Context<CommandRuntime> {
+    let env = Env::new(dir, &["run", bin]).workdir();
+    let _ = ya_runtime_sdk::build(env.clone(), |_| async move { Ok(CommandRuntime) }).await;
+    Context::<CommandRuntime>::try_with(env).unwrap()
+}
+
+#[tokio::test]
+async fn command_mode_pid_seq() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    invoke(temp_dir.path(), "ok").await;
+    invoke(temp_dir.path(), "fail").await;
+    let ctx = invoke(temp_dir.path(), "ok").await;
+
+    let pids = ctx
+        .commands()
+        .unwrap()
+        .iter()
+        .map(|r| r.pid)
+        .collect::<Vec<_>>();
+    assert_eq!(pids, vec![0, 1, 2]);
+
+    let record = ctx.command_record(1).unwrap().unwrap();
+    assert_eq!(record.pid, 1);
+    assert_eq!(record.state, CommandState::Finished);
+    assert_eq!(record.return_code, Some(1));
+
+    let record = ctx.command_record(2).unwrap().unwrap();
+    assert_eq!(record.return_code, Some(0));
+    assert_eq!(ctx.command_record(3).unwrap(), None);
+}
+
+#[test]
+fn command_mode_pid_seq_concurrent() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let count: ProcessId = 8;
+
+    let threads = (0..count)
+        .map(|_| {
+            let dir = temp_dir.path().to_path_buf();
+            std::thread::spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap();
+                rt.block_on(invoke(&dir, "ok"));
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let env = Env::new(temp_dir.path(), &["run"]).workdir();
+    let ctx = Context::<CommandRuntime>::try_with(env).unwrap();
+    let pids = ctx
+        .commands()
+        .unwrap()
+        .iter()
+        .map(|r| r.pid)
+        .collect::<Vec<_>>();
+    assert_eq!(pids, (0..count).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn command_mode_status() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    invoke(temp_dir.path(), "echo").await;
+    let ctx = invoke(temp_dir.path(), "fail").await;
+
+    let record = ctx.command_status(0).unwrap().unwrap();
+    assert_eq!(record.state, CommandState::Finished);
+    assert_eq!(record.return_code, Some(0));
+    assert_eq!(record.stdout, b"\"output\"".to_vec());
+    assert!(record.started_at.is_some());
+    assert!(record.finished_at >= record.started_at);
+
+    let records = ctx.commands().unwrap();
+    let pids = records.iter().map(|r| r.pid).collect::<Vec<_>>();
+    assert_eq!(pids, vec![0, 1]);
+    assert_eq!(records[1].return_code, Some(1));
+    assert_eq!(ctx.command_status(2).unwrap(), None);
+}
+
+#[tokio::test]
+async fn command_mode_prune() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let env = Env::new(temp_dir.path(), &["run"]).workdir();
+    let ctx = Context::<CommandRuntime>::try_with(env).unwrap();
+    let store = ctx.state_store().unwrap();
+
+    let max: ProcessId = 1024;
+    for pid in 0..max {
+        let record = CommandRecord {
+            pid,
+            state: CommandState::Finished,
+            return_code: Some(0),
+            started_at: None,
+            finished_at: None,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        store
+            .set(&format!("ya-runtime-sdk.command.{}", pid), &record)
+            .unwrap();
+    }
+    store.set("ya-runtime-sdk.pid-seq", &max).unwrap();
+
+    let ctx = invoke(temp_dir.path(), "ok").await;
+    let pids = ctx
+        .commands()
+        .unwrap()
+        .iter()
+        .map(|r| r.pid)
+        .collect::<Vec<_>>();
+    assert_eq!(pids.len(), max as usize);
+    assert_eq!(pids.first(), Some(&1));
+    assert_eq!(pids.last(), Some(&max));
+}
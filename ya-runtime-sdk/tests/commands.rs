@@ -0,0 +1,55 @@
+use structopt::StructOpt;
+use ya_runtime_sdk::*;
+
+#[derive(StructOpt, Debug, PartialEq)]
+struct CopyArgs {
+    source: String,
+    target: String,
+    #[structopt(long)]
+    overwrite: bool,
+}
+
+#[derive(RuntimeCommands, Debug, PartialEq)]
+enum Commands {
+    Copy(CopyArgs),
+    #[command(name = "ls")]
+    ListFiles,
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn commands_parse() {
+    assert_eq!(Commands::names(), &["copy", "ls"]);
+
+    let command = RunProcess {
+        bin: "copy".to_string(),
+        args: args(&["/in/a", "/out/b", "--overwrite"]),
+        ..Default::default()
+    };
+    assert_eq!(
+        Commands::from_run_process(&command).unwrap(),
+        Commands::Copy(CopyArgs {
+            source: "/in/a".to_string(),
+            target: "/out/b".to_string(),
+            overwrite: true,
+        })
+    );
+    assert_eq!(Commands::parse("ls", &[]).unwrap(), Commands::ListFiles);
+}
+
+#[test]
+fn commands_parse_err() {
+    let err = Commands::parse("copy", &args(&["/in/a"])).unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::InvalidArgument));
+    assert!(err.message().contains("<target>"));
+
+    let err = Commands::parse("ls", &args(&["-l"])).unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::InvalidArgument));
+
+    let err = Commands::parse("rm", &[]).unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::NotSupported));
+    assert!(err.message().contains("copy, ls"));
+}
@@ -128,3 +128,19 @@ fn conf_file_err() {
     let read = Context::<Runtime>::read_config(&renamed_path);
     assert_eq!(read.is_ok(), false);
 }
+
+#[test]
+fn context_state_store() {
+    let env = Env::default();
+    let dir = env.temp_dir.path().to_path_buf();
+
+    let context = Context::<Runtime>::try_with(env).expect("Failed to initialize runtime context");
+    let store = context
+        .state_store()
+        .expect("Failed to open the state store");
+    store.set("key", &Conf::default()).unwrap();
+
+    let reopened = StateStore::new(&dir);
+    assert_eq!(reopened.get("key").unwrap(), Some(Conf::default()));
+    assert!(store.path().starts_with(&dir));
+}
@@ -0,0 +1,145 @@
+#![cfg(feature = "debugger")]
+
+use futures::FutureExt;
+use std::cell::RefCell;
+use ya_runtime_sdk::debugger::*;
+use ya_runtime_sdk::runtime_api::server::*;
+
+#[derive(Default)]
+struct Service {
+    requests: RefCell<Vec<String>>,
+}
+
+impl RuntimeService for Service {
+    fn hello(&self, version: &str) -> AsyncResponse<'_, String> {
+        futures::future::ok(version.to_string()).boxed_local()
+    }
+
+    fn run_process(&self, run: RunProcess) -> AsyncResponse<'_, RunProcessResp> {
+        self.requests.borrow_mut().push(format!("run {}", run.bin));
+        futures::future::ok(RunProcessResp { pid: 7 }).boxed_local()
+    }
+
+    fn kill_process(&self, kill: KillProcess) -> AsyncResponse<'_, ()> {
+        self.requests
+            .borrow_mut()
+            .push(format!("kill {} {}", kill.pid, kill.signal));
+        let err = ErrorResponse {
+            code: 2,
+            message: "not found".to_string(),
+            ..Default::default()
+        };
+        futures::future::err(err).boxed_local()
+    }
+
+    fn create_network(&self, network: CreateNetwork) -> AsyncResponse<'_, CreateNetworkResp> {
+        self.requests
+            .borrow_mut()
+            .push(format!("network {}", network.networks[0].addr));
+        futures::future::ok(CreateNetworkResp::default()).boxed_local()
+    }
+
+    fn shutdown(&self) -> AsyncResponse<'_, ()> {
+        self.requests.borrow_mut().push("shutdown".to_string());
+        futures::future::ok(()).boxed_local()
+    }
+}
+
+#[test]
+fn debugger_parse() {
+    assert_eq!(DebugCommand::parse("  \n", "/work").unwrap(), None);
+
+    let command = DebugCommand::parse(r#"run /bin/sh -c "echo 'a b'" c\ d"#, "/work");
+    match command.unwrap() {
+        Some(DebugCommand::Run(run)) => {
+            assert_eq!(run.bin, "/bin/sh");
+            assert_eq!(run.args, vec!["-c", "echo 'a b'", "c d"]);
+            assert_eq!(run.work_dir, "/work");
+            assert!(run.stdout.is_some());
+        }
+        command => panic!("unexpected command: {:?}", command),
+    }
+
+    let command = DebugCommand::parse("kill 3", "/work").unwrap();
+    assert_eq!(
+        command,
+        Some(DebugCommand::Kill(KillProcess { pid: 3, signal: 15 }))
+    );
+    let command = DebugCommand::parse("kill 3 9", "/work").unwrap();
+    assert_eq!(
+        command,
+        Some(DebugCommand::Kill(KillProcess { pid: 3, signal: 9 }))
+    );
+
+    let line =
+        "network addr=10.0.0.0 mask=255.255.255.0 if_addr=10.0.0.2 interface=inet host.10.0.0.1=gw";
+    match DebugCommand::parse(line, "/work").unwrap() {
+        Some(DebugCommand::Network(network)) => {
+            assert_eq!(network.networks.len(), 1);
+            assert_eq!(network.networks[0].addr, "10.0.0.0");
+            assert_eq!(network.networks[0].if_addr, "10.0.0.2");
+            assert_eq!(network.interface, NetworkInterface::Inet as i32);
+            assert_eq!(
+                network.hosts.get("10.0.0.1").map(String::as_str),
+                Some("gw")
+            );
+        }
+        command => panic!("unexpected command: {:?}", command),
+    }
+
+    for line in [
+        "run",
+        "kill",
+        "kill x",
+        "network addr=10.0.0.0",
+        "network mask",
+        "run \"unterminated",
+        "unknown",
+    ] {
+        assert!(DebugCommand::parse(line, "/work").is_err(), "{}", line);
+    }
+}
+
+#[tokio::test]
+async fn debugger_repl() {
+    let service = Service::default();
+    let input = b"help\nrun echo\nbogus\nkill 7\nnetwork addr=10.0.0.0 mask=255.0.0.0\nshutdown\nrun ignored\n";
+    let mut output = Vec::new();
+
+    repl(&service, &input[..], &mut output, "/work")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *service.requests.borrow(),
+        vec!["run echo", "kill 7 15", "network 10.0.0.0", "shutdown"]
+    );
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("> pid: 7\n"));
+    assert!(output.contains("> error: Unknown command (code 22; command: bogus)\n"));
+    assert!(output.contains("> error: not found (code 2)\n"));
+    assert!(output.contains("> ok\n"));
+}
+
+#[test]
+fn debugger_format() {
+    let status = ProcessStatus {
+        pid: 2,
+        running: false,
+        return_code: 1,
+        stdout: b"out 1\nout 2\n".to_vec(),
+        stderr: b"err".to_vec(),
+    };
+    assert_eq!(
+        format_process_status(&status),
+        "[2] stdout: out 1\n[2] stdout: out 2\n[2] stderr: err\n[2] exited with code 1"
+    );
+
+    let status = RuntimeStatus {
+        kind: Some(RuntimeStatusKind::Counter(RuntimeCounter {
+            name: "cpu".to_string(),
+            value: 1.5,
+        })),
+    };
+    assert_eq!(format_runtime_status(&status), "[runtime] counter cpu: 1.5");
+}
@@ -0,0 +1,62 @@
+use ya_runtime_sdk::{Error, ErrorKind, ErrorResponse, RuntimeError};
+
+#[derive(Debug, RuntimeError)]
+#[runtime_error(kind = "invalid_argument")]
+enum RuntimeErr {
+    Argument(String),
+    #[runtime_error(kind = "not_found")]
+    Missing {
+        path: String,
+    },
+    #[runtime_error(code = 42)]
+    Custom,
+}
+
+impl std::fmt::Display for RuntimeErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Argument(arg) => write!(f, "invalid argument: {}", arg),
+            Self::Missing { path } => write!(f, "missing: {}", path),
+            Self::Custom => write!(f, "custom"),
+        }
+    }
+}
+
+#[test]
+fn error_kind() {
+    let err = Error::not_found("no such file").with_context("path", "/in/file");
+
+    assert_eq!(err.kind(), Some(ErrorKind::NotFound));
+    assert_eq!(err.code(), ErrorKind::NotFound.code());
+    assert_eq!(err.message(), "no such file");
+    assert_eq!(err.context().get("path").unwrap(), "/in/file");
+
+    let err = Error::from(std::io::Error::from(std::io::ErrorKind::TimedOut));
+    assert_eq!(err.kind(), Some(ErrorKind::Timeout));
+}
+
+#[test]
+fn error_anyhow_chain() {
+    let source = anyhow::anyhow!("root cause").context("intermediate");
+    let err = Error::from(source.context("top level"));
+
+    assert_eq!(err.message(), "top level");
+    assert_eq!(err.context().get("source.0").unwrap(), "intermediate");
+    assert_eq!(err.context().get("source.1").unwrap(), "root cause");
+}
+
+#[test]
+fn error_derive() {
+    let err = Error::from(RuntimeErr::Argument("-x".to_string()));
+    assert_eq!(err.kind(), Some(ErrorKind::InvalidArgument));
+    assert_eq!(err.message(), "invalid argument: -x");
+
+    let err = Error::from(RuntimeErr::Missing {
+        path: "/in".to_string(),
+    });
+    assert_eq!(err.kind(), Some(ErrorKind::NotFound));
+
+    let response = ErrorResponse::from(RuntimeErr::Custom);
+    assert_eq!(response.code, 42);
+    assert_eq!(response.message, "custom");
+}
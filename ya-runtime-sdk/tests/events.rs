@@ -0,0 +1,120 @@
+mod utils;
+
+use futures::channel::mpsc::Receiver;
+use utils::{Env, Events};
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct EventRuntime;
+
+impl_empty_runtime!(EventRuntime, {
+    fn run_command<'a>(
+        &mut self,
+        command: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        ctx.command(move |mut run_ctx| async move {
+            run_ctx.stdout(command.bin).await;
+            run_ctx.counter("golem.usage.custom".to_string(), 1.).await;
+            Ok(())
+        })
+    }
+});
+
+fn drain(rx: &mut Receiver<EventKind>) -> Vec<EventKind> {
+    std::iter::from_fn(|| rx.try_next().ok().flatten()).collect()
+}
+
+fn command(bin: &str) -> RunProcess {
+    RunProcess {
+        bin: bin.to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn event_subscription() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let mut ctx = Context::<EventRuntime>::try_with(Env::temp(&["start"])).unwrap();
+            let events = Events::default();
+            let emitter = events.emitter();
+            let mut all = emitter.subscribe(EventFilter::default());
+            let filter = EventFilter::default().event_type(EventType::Counter);
+            let mut counters = emitter.subscribe(filter);
+            let mut first = emitter.subscribe(EventFilter::default().pid(0));
+            ctx.emitter = Some(emitter);
+
+            let mode = RuntimeMode::Server;
+            let pid = EventRuntime.run_command(command("a"), mode, &mut ctx);
+            assert_eq!(pid.await.unwrap(), 0);
+            let pid = EventRuntime.run_command(command("b"), mode, &mut ctx);
+            assert_eq!(pid.await.unwrap(), 1);
+            events.stopped_count(2).await;
+
+            // started, stdout, counter and stopped events of both commands
+            assert_eq!(drain(&mut all).len(), 8);
+
+            let counters = drain(&mut counters);
+            assert_eq!(counters.len(), 2);
+            assert!(counters
+                .iter()
+                .all(|e| e.event_type() == Some(EventType::Counter)));
+
+            let first = drain(&mut first);
+            let stdout = first
+                .iter()
+                .filter_map(|e| match e {
+                    EventKind::Process(status) => Some(status.stdout.clone()),
+                    EventKind::Runtime(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .concat();
+            assert_eq!(first.len(), 3);
+            assert!(first.iter().all(|e| e.pid() == Some(0)));
+            assert_eq!(stdout, b"a".to_vec());
+        })
+        .await;
+}
+
+fn counter(value: f64) -> RuntimeStatusKind {
+    RuntimeStatusKind::Counter(RuntimeCounter {
+        name: "golem.usage.custom".to_string(),
+        value,
+    })
+}
+
+#[tokio::test]
+async fn event_subscription_delivery() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let mut emitter = events.emitter();
+            let mut all = emitter.subscribe(EventFilter::default());
+
+            // events are broadcast once delivered
+            drop(emitter.emit(counter(0.)));
+            emitter.emit(counter(1.)).await;
+            emitter.flush().await;
+            assert_eq!(events.runtime.lock().unwrap().len(), 1);
+            assert_eq!(drain(&mut all).len(), 1);
+
+            // events exceeding the buffer of a lagging subscriber are dropped
+            let count = EventEmitter::SUBSCRIBER_BUFFER + 10;
+            for i in 0..count {
+                emitter.emit(counter(i as f64)).await;
+            }
+            emitter.flush().await;
+            assert_eq!(events.runtime.lock().unwrap().len(), count + 1);
+            assert_eq!(drain(&mut all).len(), EventEmitter::SUBSCRIBER_BUFFER);
+
+            // and delivered again once received
+            emitter.emit(counter(0.)).await;
+            emitter.flush().await;
+            assert_eq!(drain(&mut all).len(), 1);
+        })
+        .await;
+}
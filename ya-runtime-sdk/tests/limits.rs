@@ -0,0 +1,135 @@
+#![cfg(unix)]
+
+mod utils;
+
+use std::time::Duration;
+use tokio::process::Command;
+use utils::{Env, Events};
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct Runtime;
+impl_empty_runtime!(Runtime);
+
+/// Runs `command`, returning its exit code and the return code of the stopped event
+async fn run(limits: Limits, command: Command) -> (i32, i32) {
+    let events = Events::default();
+    let mut ctx = Context::<Runtime>::try_with(Env::temp(&["test"])).unwrap();
+    ctx.emitter = Some(events.emitter());
+    ctx.limits = limits;
+
+    let (tx, rx) = futures::channel::oneshot::channel();
+    let pid = ctx
+        .command(move |run_ctx| async move {
+            let mut child = run_ctx.spawn(command)?;
+            let _ = tx.send(child.wait().await?);
+            Ok(())
+        })
+        .await
+        .unwrap();
+    let code = rx.await.unwrap();
+    (code, events.stopped(pid).await.return_code)
+}
+
+#[tokio::test]
+async fn limits_none() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let mut command = Command::new("sh");
+            command.args(["-c", "exit 3"]);
+            assert_eq!(run(Limits::default(), command).await, (3, 3));
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn limits_cpu_time() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let limits = Limits {
+                cpu_time: Some(Duration::from_secs(1)),
+                ..Default::default()
+            };
+            let mut command = Command::new("sh");
+            command.args(["-c", "while :; do :; done"]);
+            let expected = (RETURN_CODE_CPU_LIMIT, RETURN_CODE_CPU_LIMIT);
+            assert_eq!(run(limits, command).await, expected);
+        })
+        .await;
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn limits_cpu_time_hard() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let limits = Limits {
+                cpu_time: Some(Duration::from_secs(1)),
+                ..Default::default()
+            };
+            // SIGXCPU is ignored, thus the process is killed on reaching the hard limit
+            let mut command = Command::new("sh");
+            command.args(["-c", "trap '' XCPU; while :; do :; done"]);
+            let expected = (RETURN_CODE_CPU_LIMIT, RETURN_CODE_CPU_LIMIT);
+            assert_eq!(run(limits, command).await, expected);
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn limits_file_size() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let limits = Limits {
+                file_size: Some(1024),
+                ..Default::default()
+            };
+            let output = temp_dir.path().join("output");
+            let mut command = Command::new("dd");
+            command
+                .arg("if=/dev/zero")
+                .arg(format!("of={}", output.display()))
+                .args(["bs=4096", "count=1"])
+                .stderr(std::process::Stdio::null());
+            let expected = (RETURN_CODE_FILE_SIZE_LIMIT, RETURN_CODE_FILE_SIZE_LIMIT);
+            assert_eq!(run(limits, command).await, expected);
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn limits_return_code() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let mut ctx = Context::<Runtime>::try_with(Env::temp(&["test"])).unwrap();
+            ctx.emitter = Some(events.emitter());
+
+            let pid = ctx
+                .command(|run_ctx| async move {
+                    run_ctx.set_return_code(RETURN_CODE_MEMORY_LIMIT);
+                    Ok(())
+                })
+                .await
+                .unwrap();
+            let status = events.stopped(pid).await;
+            assert_eq!(status.return_code, RETURN_CODE_MEMORY_LIMIT);
+
+            // errors are never reported with a successful return code
+            let pid = ctx
+                .command(|run_ctx| async move {
+                    run_ctx.set_return_code(0);
+                    Err(Error::internal("failure"))
+                })
+                .await
+                .unwrap();
+            assert_eq!(events.stopped(pid).await.return_code, 1);
+        })
+        .await;
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "logger")]
+
+mod utils;
+
+use utils::{Env, Events};
+use ya_runtime_sdk::logger::LoggerConfig;
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct Runtime;
+impl_empty_runtime!(Runtime);
+
+fn config(size: &str) -> anyhow::Result<LoggerConfig> {
+    let size = size.to_string();
+    LoggerConfig::from_vars("logs", move |name| {
+        (name == "YA_RUNTIME_LOG_ROTATE_SIZE").then(|| size.clone())
+    })
+}
+
+#[test]
+fn rotate_size() {
+    assert_eq!(config("2K").unwrap().rotate_size, Some(2048));
+    assert_eq!(config("0").unwrap().rotate_size, None);
+    assert!(config("99999999999999G").is_err());
+}
+
+#[tokio::test]
+async fn command_log_fallback() {
+    // the fallback logger is the only one started by this test binary
+    let _guard = ya_runtime_sdk::logger::start(Err(anyhow::anyhow!("no log directory")));
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let path = temp_dir.path().join("command.log");
+
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let mut ctx = Context::<Runtime>::try_with(Env::temp(&["start"])).unwrap();
+            ctx.emitter = Some(events.emitter());
+
+            let log_path = path.clone();
+            let pid = ctx
+                .command(move |mut run_ctx| async move {
+                    run_ctx.log_to_file(&log_path)?;
+                    run_ctx.log_to_stderr(log::LevelFilter::Warn);
+                    log::info!("to file only");
+                    log::warn!("to file and stderr");
+                    Ok(())
+                })
+                .await
+                .unwrap();
+            log::warn!("outside of the command");
+
+            events.stopped(pid).await;
+            let stderr = events.stderr(pid).concat();
+            assert!(stderr.contains(&format!("pid={}] to file and stderr", pid)));
+            assert!(!stderr.contains("to file only"));
+            assert!(!stderr.contains("outside"));
+        })
+        .await;
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(contents.contains("to file only"));
+    assert!(contents.contains("to file and stderr"));
+    assert!(!contents.contains("outside"));
+}
@@ -0,0 +1,148 @@
+mod utils;
+
+use tokio::io::AsyncWriteExt;
+use utils::{Env, Events};
+use ya_runtime_sdk::runtime_api::server::proto::Output;
+use ya_runtime_sdk::runtime_api::server::RuntimeService;
+use ya_runtime_sdk::server::Server;
+use ya_runtime_sdk::*;
+
+/// "abżc\nd€" split within multi-byte characters, followed by an invalid byte
+const CHUNKS: &[&[u8]] = &[b"ab\xC5", b"\xBCc\nd\xE2\x82", b"\xAC\xFF"];
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct PipeRuntime {
+    events: Events,
+}
+
+impl_empty_runtime!(PipeRuntime, {
+    fn run_command<'a>(
+        &mut self,
+        command: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        let request = command.clone();
+        let events = self.events.clone();
+        ctx.command_for(&request, move |mut run_ctx| async move {
+            let encoding = match command.bin.as_str() {
+                "utf8" => Encoding::Utf8,
+                "lines" => Encoding::Lines,
+                "base64" => Encoding::Base64,
+                "progress" => {
+                    let pid = *run_ctx.id();
+                    let (mut writer, reader) = tokio::io::duplex(64);
+                    let progress = async move {
+                        writer.write_all(b"50%\r").await?;
+                        // emitted before the line is complete
+                        events.stdout_chunk(pid, "50%\r").await;
+                        writer.write_all(b"100%\n").await
+                    };
+                    let (piped, written) = futures::join!(run_ctx.pipe_stdout(reader), progress);
+                    written?;
+                    return piped;
+                }
+                _ => {
+                    let stdout = command.args[1..].join("");
+                    run_ctx.pipe_stdout(stdout.as_bytes()).await?;
+                    run_ctx.pipe_stderr(command.args[0].as_bytes()).await?;
+                    return Ok(());
+                }
+            };
+            run_ctx.set_stdout_encoding(encoding);
+            for chunk in CHUNKS {
+                run_ctx.stdout(*chunk).await;
+            }
+            Ok(())
+        })
+    }
+});
+
+fn command(stdout: &[&str], stderr: &str, capture: Capture) -> RunProcess {
+    let args = std::iter::once(&stderr).chain(stdout.iter());
+    RunProcess {
+        bin: "pipe".to_string(),
+        args: args.map(|s| s.to_string()).collect(),
+        stdout: Some(Output::from(capture.clone())),
+        stderr: Some(Output::from(capture)),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn pipe_output() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let mut ctx = Context::<PipeRuntime>::try_with(Env::temp(&["start"])).unwrap();
+            ctx.emitter = Some(events.emitter());
+            let runtime = PipeRuntime {
+                events: events.clone(),
+            };
+            let server = Server::new(runtime, ctx);
+
+            // chunked by newline and size
+            let run = command(&["ab\ncd", "efghijk\nl"], "err", Capture::Stream(4));
+            let pid = server.run_process(run).await.unwrap().pid;
+            events.stopped(pid).await;
+            assert_eq!(events.stdout(pid), vec!["ab\n", "cdef", "ghij", "k\n", "l"]);
+            assert_eq!(events.stderr(pid), vec!["err"]);
+
+            // trailing bytes only
+            let run = command(&["abc\n", "def"], "error", Capture::AtEnd(3));
+            let pid = server.run_process(run).await.unwrap().pid;
+            events.stopped(pid).await;
+            assert_eq!(events.stdout(pid), vec!["def"]);
+            assert_eq!(events.stderr(pid), vec!["ror"]);
+
+            // discarded
+            let run = command(&["abc"], "error", Capture::Discard);
+            let pid = server.run_process(run).await.unwrap().pid;
+            events.stopped(pid).await;
+            assert!(events.stdout(pid).is_empty());
+            assert!(events.stderr(pid).is_empty());
+
+            // partial lines are emitted as they arrive
+            let run = RunProcess {
+                bin: "progress".to_string(),
+                stdout: Some(Output::from(Capture::Stream(1024))),
+                ..Default::default()
+            };
+            let pid = server.run_process(run).await.unwrap().pid;
+            let status = events.stopped(pid).await;
+            assert_eq!(status.return_code, 0);
+            assert_eq!(events.stdout(pid), vec!["50%\r", "100%\n"]);
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn encode_output() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let mut ctx = Context::<PipeRuntime>::try_with(Env::temp(&["start"])).unwrap();
+            ctx.emitter = Some(events.emitter());
+            let server = Server::new(PipeRuntime::default(), ctx);
+
+            let run = |bin: &str| RunProcess {
+                bin: bin.to_string(),
+                ..Default::default()
+            };
+
+            let pid = server.run_process(run("utf8")).await.unwrap().pid;
+            events.stopped(pid).await;
+            assert_eq!(events.stdout(pid), vec!["ab", "żc\nd", "€\u{FFFD}"]);
+
+            let pid = server.run_process(run("lines")).await.unwrap().pid;
+            events.stopped(pid).await;
+            assert_eq!(events.stdout(pid), vec!["abżc\n", "d€\u{FFFD}"]);
+
+            let pid = server.run_process(run("base64")).await.unwrap().pid;
+            events.stopped(pid).await;
+            assert_eq!(events.stdout(pid), vec!["YWLF", "vGMKZOKC", "rP8="]);
+        })
+        .await;
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+use structopt::StructOpt;
+use ya_runtime_sdk::*;
+
+#[test]
+fn plugin_descriptor_cli() {
+    let args = [
+        "runtime",
+        "plugin-descriptor",
+        "--preset",
+        "--description",
+        "Custom runtime",
+        "--",
+        "--runtime-managed-image",
+    ];
+    let command = Command::from_iter_safe(args).unwrap();
+    assert_eq!(
+        command,
+        Command::PluginDescriptor {
+            description: Some("Custom runtime".to_string()),
+            supervisor_path: None,
+            preset: true,
+            args: vec!["--runtime-managed-image".to_string()],
+        }
+    );
+    assert_eq!(command.args(), &vec!["--runtime-managed-image".to_string()]);
+}
+
+#[test]
+fn plugin_descriptor_json() {
+    let mut descriptor =
+        PluginDescriptor::new("custom", "0.1.0", PathBuf::from("/opt/custom/runtime"));
+    descriptor.extra_args = vec!["--runtime-managed-image".to_string()];
+
+    assert_eq!(
+        serde_json::to_value(&descriptor).unwrap(),
+        serde_json::json!({
+            "name": "custom",
+            "version": "0.1.0",
+            "supervisor-path": "exe-unit",
+            "runtime-path": "/opt/custom/runtime",
+            "description": "custom runtime",
+            "extra-args": ["--runtime-managed-image"]
+        })
+    );
+}
+
+#[test]
+fn plugin_preset_json() {
+    let preset = Preset::new("custom", ["custom.requests"]);
+    assert_eq!(
+        serde_json::to_value(&preset).unwrap(),
+        serde_json::json!({
+            "name": "custom",
+            "exeunit-name": "custom",
+            "pricing-model": "linear",
+            "initial-price": 0.0,
+            "usage-coeffs": {
+                "custom.requests": 0.0,
+                "golem.usage.cpu_sec": 0.0,
+                "golem.usage.duration_sec": 0.0
+            }
+        })
+    );
+}
@@ -0,0 +1,182 @@
+mod utils;
+
+use futures::FutureExt;
+use utils::Env;
+use ya_runtime_sdk::recording::*;
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct EchoRuntime {
+    upper: bool,
+}
+
+impl_empty_runtime!(EchoRuntime, {
+    fn run_command<'a>(
+        &mut self,
+        command: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        let mut output = command.args.join(" ");
+        if self.upper {
+            output = output.to_uppercase();
+        }
+        ctx.command(move |mut run_ctx| async move {
+            run_ctx.stdout(output).await;
+            Ok(())
+        })
+    }
+});
+
+fn context(dir: &tempdir::TempDir) -> Context<EchoRuntime> {
+    let env = Env::new(dir.path(), &["start"]).workdir();
+    Context::<EchoRuntime>::try_with(env).unwrap()
+}
+
+fn requests() -> Vec<RecordEntry> {
+    let requests = vec![
+        Request::Hello {
+            version: "0.1.0".to_string(),
+        },
+        Request::RunProcess {
+            bin: "echo".to_string(),
+            args: vec!["hello".to_string(), "world".to_string()],
+            work_dir: "/".to_string(),
+            stdout: Some(Capture::Stream(1024)),
+            stderr: None,
+        },
+        Request::KillProcess { pid: 1, signal: 15 },
+        Request::Shutdown,
+    ];
+    requests
+        .into_iter()
+        .map(|request| RecordEntry {
+            timestamp: 0,
+            message: Message::Request(request),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn recording_replay() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let mut ctx = context(&temp_dir);
+            ctx.enable_recording().unwrap();
+            Replay::new(requests())
+                .run(EchoRuntime::default(), ctx)
+                .boxed_local()
+                .await;
+
+            let path = temp_dir.path().join(Recorder::FILE_NAME);
+            let contents = std::fs::read_to_string(&path).unwrap();
+            assert!(contents
+                .lines()
+                .any(|l| l.contains(r#""stdout":"hello world""#)));
+
+            let replay = Replay::load(&path).unwrap();
+            let messages = replay
+                .entries()
+                .iter()
+                .map(|e| &e.message)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                messages
+                    .iter()
+                    .filter(|m| matches!(m, Message::Request(_)))
+                    .count(),
+                4
+            );
+            assert!(messages.contains(&&Message::Response(Response::RunProcess { pid: 0 })));
+            assert!(messages
+                .iter()
+                .any(|m| matches!(m, Message::Response(Response::Error { code: 95, .. }))));
+            assert!(messages.contains(&&Message::Event(Event::ProcessStatus {
+                pid: 0,
+                running: false,
+                return_code: 0,
+                stdout: vec![],
+                stderr: vec![],
+            })));
+
+            let report = replay
+                .run(EchoRuntime::default(), context(&temp_dir))
+                .boxed_local()
+                .await;
+            assert_eq!(report.diff(), Vec::<String>::new());
+            assert!(report.is_match());
+            assert_eq!(report.actual_events.len(), 3);
+
+            let runtime = EchoRuntime { upper: true };
+            let report = replay.run(runtime, context(&temp_dir)).boxed_local().await;
+            assert!(!report.is_match());
+            let diff = report.diff();
+            assert_eq!(diff.len(), 1);
+            assert!(diff[0].starts_with("process 0 event #1: expected"));
+        })
+        .await;
+}
+
+#[test]
+fn recording_diff_concurrent() {
+    let event = |pid: ProcessId, running: bool| Event::ProcessStatus {
+        pid,
+        running,
+        return_code: 0,
+        stdout: vec![],
+        stderr: vec![],
+    };
+    let mut report = ReplayReport {
+        expected_events: vec![
+            event(0, true),
+            event(1, true),
+            event(0, false),
+            event(1, false),
+        ],
+        actual_events: vec![
+            event(1, true),
+            event(0, true),
+            event(1, false),
+            event(0, false),
+        ],
+        ..Default::default()
+    };
+    assert!(report.is_match());
+
+    // order of events of a single command is retained
+    report.actual_events.swap(1, 3);
+    assert_eq!(
+        report.diff(),
+        vec![
+            format!(
+                "process 0 event #0: expected {:?}, got {:?}",
+                event(0, true),
+                event(0, false)
+            ),
+            format!(
+                "process 0 event #1: expected {:?}, got {:?}",
+                event(0, false),
+                event(0, true)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn recording_entry_json() {
+    let entry = RecordEntry {
+        timestamp: 1,
+        message: Message::Event(Event::State {
+            name: "binary".to_string(),
+            value: vec![0xff, 0x00],
+        }),
+    };
+    let json = serde_json::to_string(&entry).unwrap();
+    assert_eq!(
+        json,
+        r#"{"timestamp":1,"event":{"type":"state","name":"binary","value":[255,0]}}"#
+    );
+    assert_eq!(serde_json::from_str::<RecordEntry>(&json).unwrap(), entry);
+}
@@ -0,0 +1,36 @@
+mod utils;
+
+use ya_runtime_sdk::*;
+
+mod default {
+    #[derive(ya_runtime_sdk::RuntimeDef, Default)]
+    pub struct Runtime;
+    crate::impl_empty_runtime!(Runtime);
+}
+
+mod custom {
+    #[derive(ya_runtime_sdk::RuntimeDef, Default)]
+    #[runtime(name = "custom-runtime", version = "1.2.3-rc.1", mode = "command")]
+    pub struct Runtime;
+    crate::impl_empty_runtime!(Runtime);
+}
+
+#[test]
+fn runtime_def_defaults() {
+    assert_eq!(default::Runtime::NAME, env!("CARGO_PKG_NAME"));
+    assert_eq!(default::Runtime::VERSION, env!("CARGO_PKG_VERSION"));
+    assert!(matches!(
+        <default::Runtime as Runtime>::MODE,
+        RuntimeMode::Server
+    ));
+}
+
+#[test]
+fn runtime_def_attributes() {
+    assert_eq!(custom::Runtime::NAME, "custom-runtime");
+    assert_eq!(custom::Runtime::VERSION, "1.2.3-rc.1");
+    assert!(matches!(
+        <custom::Runtime as Runtime>::MODE,
+        RuntimeMode::Command
+    ));
+}
@@ -0,0 +1,216 @@
+#![cfg(all(feature = "sandbox", target_os = "linux"))]
+
+mod utils;
+
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use utils::Env;
+use ya_runtime_sdk::sandbox::{SandboxPolicy, SeccompPolicy};
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct Runtime;
+impl_empty_runtime!(Runtime);
+
+/// Issues `mkdir` of the path in this variable via the x32 ABI, see `x32_mkdir`
+#[cfg(target_arch = "x86_64")]
+const X32_MKDIR: &str = "YA_RUNTIME_SDK_X32_MKDIR";
+
+async fn run(policy: SandboxPolicy, script: String) -> (i32, String) {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script);
+    run_command(policy, command).await
+}
+
+async fn run_command(policy: SandboxPolicy, mut command: Command) -> (i32, String) {
+    let mut ctx = Context::<Runtime>::try_with(Env::temp(&["test"])).unwrap();
+    ctx.sandbox = Some(policy);
+
+    let (tx, rx) = futures::channel::oneshot::channel();
+    ctx.command(move |run_ctx| async move {
+        command.stdout(Stdio::piped());
+
+        let mut child = run_ctx.spawn(command)?;
+        let mut stdout = String::new();
+        let mut pipe = child.stdout.take().unwrap();
+        pipe.read_to_string(&mut stdout).await?;
+        let _ = tx.send((child.wait().await?, stdout));
+        Ok(())
+    })
+    .await
+    .unwrap();
+    rx.await.unwrap()
+}
+
+#[tokio::test]
+async fn sandbox_namespaces() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let script = "id -u; echo $$; grep -c : /proc/net/dev".to_string();
+            let (code, stdout) = run(SandboxPolicy::default(), script).await;
+            assert_eq!(code, 0);
+            assert_eq!(stdout, "0\n1\n1\n");
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn sandbox_mounts() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let source = temp_dir.path().join("source");
+    let target = temp_dir.path().join("target");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::create_dir_all(&target).unwrap();
+    let denied = PathBuf::from("/ya-runtime-sdk-sandbox");
+
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let policy = SandboxPolicy::default().mount(&source, &target);
+            let script = format!(
+                "echo data > {}/file && touch {}",
+                target.display(),
+                denied.display()
+            );
+            let (code, _) = run(policy, script).await;
+            assert_ne!(code, 0);
+        })
+        .await;
+
+    let written = std::fs::read_to_string(source.join("file")).unwrap();
+    assert_eq!(written, "data\n");
+    assert!(!target.join("file").exists());
+    assert!(!denied.exists());
+}
+
+#[tokio::test]
+#[allow(clippy::unnecessary_cast)]
+async fn sandbox_seccomp() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let target = temp_dir.path().to_path_buf();
+
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let mut deny = vec![libc::SYS_mkdirat as i64];
+            #[cfg(target_arch = "x86_64")]
+            deny.push(libc::SYS_mkdir as i64);
+
+            let policy = SandboxPolicy {
+                read_only_root: false,
+                seccomp: Some(SeccompPolicy {
+                    deny,
+                    errno: libc::EPERM,
+                }),
+                ..Default::default()
+            }
+            .mount(&target, &target);
+            let script = format!("mkdir {}/denied", target.display());
+            let (code, _) = run(policy, script).await;
+            assert_ne!(code, 0);
+        })
+        .await;
+
+    assert!(!target.join("denied").exists());
+}
+
+#[tokio::test]
+async fn sandbox_read_only_submounts() {
+    // usually a separate, writable mount of the host
+    let dir = match tempdir::TempDir::new_in("/dev/shm", "ya-runtime-sdk") {
+        Ok(dir) => dir,
+        Err(_) => return,
+    };
+    let file = dir.path().join("file");
+
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let script = format!("touch {}", file.display());
+            let (code, _) = run(SandboxPolicy::default(), script).await;
+            assert_ne!(code, 0);
+        })
+        .await;
+
+    assert!(!file.exists());
+}
+
+#[tokio::test]
+async fn sandbox_missing_mount_point() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let source = temp_dir.path().to_path_buf();
+
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let mut ctx = Context::<Runtime>::try_with(Env::temp(&["test"])).unwrap();
+            ctx.sandbox = Some(SandboxPolicy::default().mount(source, "/ya-runtime-sdk-missing"));
+
+            let (tx, rx) = futures::channel::oneshot::channel();
+            ctx.command(move |run_ctx| async move {
+                let result = run_ctx.spawn(Command::new("true")).map(|_| ());
+                let _ = tx.send(result.map_err(|e| e.kind()));
+                Ok(())
+            })
+            .await
+            .unwrap();
+            let result = rx.await.unwrap();
+            assert_eq!(result, Err(Some(ErrorKind::InvalidArgument)));
+        })
+        .await;
+}
+
+#[cfg(target_arch = "x86_64")]
+#[tokio::test]
+#[allow(clippy::unnecessary_cast)]
+async fn sandbox_seccomp_x32() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let target = temp_dir.path().to_path_buf();
+
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let policy = SandboxPolicy {
+                read_only_root: false,
+                seccomp: Some(SeccompPolicy {
+                    deny: vec![libc::SYS_mkdir as i64],
+                    // distinct from ENOSYS, returned when the x32 ABI is not supported
+                    errno: libc::EXDEV,
+                }),
+                ..Default::default()
+            }
+            .mount(&target, &target);
+
+            let mut command = Command::new(std::env::current_exe().unwrap());
+            command
+                .args(["x32_mkdir", "--exact", "--ignored", "--quiet"])
+                .env(X32_MKDIR, target.join("denied"));
+            let (code, _) = run_command(policy, command).await;
+            assert_eq!(code, libc::EXDEV);
+        })
+        .await;
+
+    assert!(!target.join("denied").exists());
+}
+
+/// Executed by `sandbox_seccomp_x32` in the sandbox, exits with the resulting error number
+#[cfg(target_arch = "x86_64")]
+#[test]
+#[ignore]
+fn x32_mkdir() {
+    use std::os::unix::ffi::OsStringExt;
+
+    let path = match std::env::var_os(X32_MKDIR) {
+        Some(path) => std::ffi::CString::new(path.into_vec()).unwrap(),
+        None => return,
+    };
+    let nr = libc::SYS_mkdir | 0x4000_0000;
+    let code = match unsafe { libc::syscall(nr, path.as_ptr(), 0o755) } {
+        0 => 0,
+        _ => std::io::Error::last_os_error().raw_os_error().unwrap_or(1),
+    };
+    std::process::exit(code);
+}
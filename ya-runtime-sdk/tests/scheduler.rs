@@ -0,0 +1,158 @@
+mod utils;
+
+use std::time::Duration;
+use utils::{Env, Events};
+use ya_runtime_sdk::runtime_api::server::RuntimeService;
+use ya_runtime_sdk::serialize::json;
+use ya_runtime_sdk::server::Server;
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct QueueRuntime;
+
+impl_empty_runtime!(QueueRuntime, {
+    fn run_command<'a>(
+        &mut self,
+        command: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        let millis = command.bin.parse().unwrap();
+        ctx.command_for(&command, move |_| async move {
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+            Ok(())
+        })
+    }
+
+    fn command_priority(command: &RunProcess, _: &Context<Self>) -> i32 {
+        command
+            .args
+            .first()
+            .map(|p| p.parse().unwrap())
+            .unwrap_or_default()
+    }
+});
+
+/// Scheduler queue depth updates
+fn queue(events: &Events) -> Vec<json::Value> {
+    let runtime = events.runtime.lock().unwrap();
+    runtime
+        .iter()
+        .filter_map(|status| match &status.kind {
+            Some(RuntimeStatusKind::State(state)) if state.name == Scheduler::STATE => {
+                Some(json::from_slice(&state.value).unwrap())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn command(millis: u64, priority: i32) -> RunProcess {
+    RunProcess {
+        bin: millis.to_string(),
+        args: vec![priority.to_string()],
+        ..Default::default()
+    }
+}
+
+async fn with_server<F, Fut>(scheduler: Scheduler, f: F)
+where
+    F: FnOnce(Server<QueueRuntime>, Events) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let mut ctx = Context::<QueueRuntime>::try_with(Env::temp(&["start"])).unwrap();
+            ctx.emitter = Some(events.emitter());
+            ctx.scheduler = scheduler;
+            f(Server::new(QueueRuntime, ctx), events).await
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn scheduler_fifo() {
+    let scheduler = Scheduler::new(1).queue_limit(1);
+    with_server(scheduler.clone(), |server, events| async move {
+        let first = server.run_process(command(100, 0)).await.unwrap().pid;
+        let second = server.run_process(command(0, 0)).await.unwrap().pid;
+        assert_eq!(
+            scheduler.depth(),
+            QueueDepth {
+                running: 1,
+                queued: 1
+            }
+        );
+
+        let err = Error::from(server.run_process(command(0, 0)).await.unwrap_err());
+        assert_eq!(err.kind(), Some(ErrorKind::Busy));
+
+        events.stopped_count(2).await;
+        assert_eq!(
+            events.lifecycle(),
+            vec![
+                (first, true),
+                (first, false),
+                (second, true),
+                (second, false)
+            ]
+        );
+        assert_eq!(
+            queue(&events),
+            vec![
+                json::json!({"running": 1, "queued": 1}),
+                json::json!({"running": 1, "queued": 0}),
+            ]
+        );
+        assert_eq!(scheduler.depth(), QueueDepth::default());
+
+        // the rejected request has not been assigned a process id
+        let third = server.run_process(command(0, 0)).await.unwrap().pid;
+        assert_eq!(third, second + 1);
+        events.stopped(third).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn scheduler_priority() {
+    let scheduler = Scheduler::new(1).order(QueueOrder::Priority);
+    with_server(scheduler, |server, events| async move {
+        let mut pids = Vec::new();
+        for (millis, priority) in [(100, 0), (0, 1), (0, 5), (0, 1)] {
+            pids.push(
+                server
+                    .run_process(command(millis, priority))
+                    .await
+                    .unwrap()
+                    .pid,
+            );
+        }
+
+        events.stopped_count(pids.len()).await;
+        let started = events
+            .lifecycle()
+            .into_iter()
+            .filter_map(|(pid, running)| running.then_some(pid))
+            .collect::<Vec<_>>();
+        assert_eq!(started, vec![pids[0], pids[2], pids[1], pids[3]]);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn scheduler_unlimited() {
+    with_server(Scheduler::default(), |server, events| async move {
+        for _ in 0..4 {
+            server.run_process(command(100, 0)).await.unwrap();
+        }
+        let running = events.lifecycle().iter().filter(|(_, r)| *r).count();
+        assert_eq!(running, 4);
+
+        events.stopped_count(4).await;
+        assert!(queue(&events).is_empty());
+    })
+    .await;
+}
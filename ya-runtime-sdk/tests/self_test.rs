@@ -0,0 +1,123 @@
+mod utils;
+
+use std::time::Duration;
+use utils::Env;
+use ya_runtime_sdk::*;
+
+fn check(report: &SelfTestReport, name: &str) -> CheckReport {
+    report
+        .checks
+        .iter()
+        .find(|c| c.name == name)
+        .cloned()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn self_test_report() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let self_test = SelfTest::new()
+        .timeout(Duration::from_millis(100))
+        .check(Check::new("pass", async { Ok(CheckOutcome::default()) }))
+        .check(Check::new("skip", async {
+            Ok(CheckOutcome::Skip("not applicable".to_string()))
+        }))
+        .check(Check::new("optional", async { Err(Error::internal("failure")) }).optional())
+        .check(Check::writable(temp_dir.path()))
+        .check(Check::exists(temp_dir.path()));
+
+    let report = self_test.run().await;
+    assert!(report.passed);
+    assert_eq!(report.failed().count(), 0);
+    assert_eq!(report.checks.len(), 5);
+
+    let c = check(&report, "pass");
+    assert_eq!(c.status, CheckStatus::Pass);
+    assert!(c.required);
+    let c = check(&report, "skip");
+    assert_eq!(c.status, CheckStatus::Skip);
+    assert_eq!(c.message.as_deref(), Some("not applicable"));
+    let c = check(&report, "optional");
+    assert_eq!(c.status, CheckStatus::Fail);
+    assert!(!c.required);
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["passed"], true);
+    assert_eq!(json["checks"][0]["status"], "pass");
+    assert!(json["checks"][0].get("message").is_none());
+    assert_eq!(json["checks"][1]["status"], "skip");
+    assert_eq!(json["checks"][2]["status"], "fail");
+    assert_eq!(json["checks"][2]["message"], "failure");
+}
+
+#[tokio::test]
+async fn self_test_failure() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let self_test = SelfTest::new()
+        .check(
+            Check::new("timeout", async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(CheckOutcome::default())
+            })
+            .timeout(Duration::from_millis(50)),
+        )
+        .check(Check::new("panic", async { panic!("check panic") }))
+        .check(Check::binary("ya-runtime-sdk-missing-binary"))
+        .check(Check::exists(temp_dir.path().join("missing")))
+        .check(Check::new("pass", async { Ok(CheckOutcome::default()) }));
+
+    let report = self_test.run().await;
+    assert!(!report.passed);
+    assert_eq!(report.failed().count(), 4);
+    assert_eq!(
+        check(&report, "timeout").message.as_deref(),
+        Some("Timed out after 50 ms")
+    );
+    assert_eq!(
+        check(&report, "panic").message.as_deref(),
+        Some("Check panicked: check panic")
+    );
+    assert_eq!(
+        check(&report, "binary:ya-runtime-sdk-missing-binary").status,
+        CheckStatus::Fail
+    );
+    assert_eq!(check(&report, "pass").status, CheckStatus::Pass);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn self_test_binary() {
+    let report = SelfTest::new().check(Check::binary("sh")).run().await;
+    assert!(report.passed);
+    assert_eq!(report.checks[0].name, "binary:sh");
+}
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct TestRuntime;
+
+impl_empty_runtime!(TestRuntime, {
+    fn run_command<'a>(
+        &mut self,
+        _: RunProcess,
+        _: RuntimeMode,
+        _: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        unimplemented!()
+    }
+
+    fn self_test(&mut self, _: &mut Context<Self>) -> SelfTest {
+        SelfTest::new()
+            .check(Check::new("required", async {
+                Err(Error::internal("failure"))
+            }))
+            .check(Check::new("optional", async { Err(Error::internal("failure")) }).optional())
+    }
+});
+
+#[tokio::test]
+async fn self_test_command() {
+    let err = ya_runtime_sdk::run_with::<TestRuntime, _>(Env::temp(&["test"]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Self-test failed: required");
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use ya_runtime_sdk::*;
+
+#[derive(Deserialize, Serialize, Debug, Eq, PartialEq)]
+struct Deployment {
+    image: String,
+    volumes: Vec<String>,
+}
+
+#[test]
+fn state_store() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let store = StateStore::new(temp_dir.path());
+    let deployment = Deployment {
+        image: "image.gvmi".to_string(),
+        volumes: vec!["/in".to_string(), "/out".to_string()],
+    };
+
+    assert_eq!(store.get::<Deployment>("deployment").unwrap(), None);
+    assert!(store.keys().unwrap().is_empty());
+
+    store.set("deployment", &deployment).unwrap();
+    store.set("counter", &1u64).unwrap();
+    assert_eq!(store.get("deployment").unwrap(), Some(deployment));
+    assert_eq!(store.keys().unwrap(), vec!["counter", "deployment"]);
+
+    let reopened = StateStore::new(temp_dir.path());
+    let counter = reopened
+        .update("counter", |v: Option<u64>| v.unwrap_or_default() + 1)
+        .unwrap();
+    assert_eq!(counter, 2);
+    assert_eq!(store.get::<u64>("counter").unwrap(), Some(2));
+
+    assert!(store.remove("counter").unwrap());
+    assert!(!store.remove("counter").unwrap());
+    assert!(!store.contains("counter").unwrap());
+
+    let entries = std::fs::read_dir(store.path()).unwrap().count();
+    assert_eq!(entries, 1);
+}
+
+#[test]
+fn state_store_err() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let store = StateStore::new(temp_dir.path());
+
+    for key in ["", ".hidden", "../escape", "dir/key"] {
+        let err = store.set(key, &0).unwrap_err();
+        assert_eq!(err.kind(), Some(ErrorKind::InvalidArgument));
+    }
+
+    std::fs::create_dir_all(store.path()).unwrap();
+    std::fs::write(store.path().join("invalid.json"), "{").unwrap();
+    let err = store.get::<u64>("invalid").unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::Internal));
+    assert_eq!(
+        err.context().get("key").map(String::as_str),
+        Some("invalid")
+    );
+}
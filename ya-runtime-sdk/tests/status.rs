@@ -0,0 +1,100 @@
+mod utils;
+
+use std::time::Duration;
+use utils::{Env, Events};
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct StatusRuntime;
+
+impl_empty_runtime!(StatusRuntime, {
+    fn run_command<'a>(
+        &mut self,
+        command: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        ctx.command(move |mut run_ctx| async move {
+            for line in command.args {
+                run_ctx.stderr(line).await;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            match command.bin.as_str() {
+                "fail" => Err(Error::internal("failure")),
+                _ => Ok(()),
+            }
+        })
+    }
+});
+
+fn command(bin: &str, args: &[&str]) -> RunProcess {
+    RunProcess {
+        bin: bin.to_string(),
+        args: args.iter().map(|s| s.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn command_status() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let mut ctx = Context::<StatusRuntime>::try_with(Env::temp(&["start"])).unwrap();
+            ctx.emitter = Some(events.emitter());
+            ctx.scheduler = Scheduler::new(1);
+
+            let mode = RuntimeMode::Server;
+            let first = StatusRuntime.run_command(command("ok", &["a", "b"]), mode, &mut ctx);
+            let first = first.await.unwrap();
+            let second = StatusRuntime.run_command(command("fail", &[]), mode, &mut ctx);
+            let second = second.await.unwrap();
+            tokio::task::yield_now().await;
+
+            let record = ctx.command_status(first).unwrap().unwrap();
+            assert_eq!(record.state, CommandState::Running);
+            assert_eq!(record.stderr, b"ab".to_vec());
+            assert!(record.started_at.is_some());
+            assert_eq!(record.finished_at, None);
+
+            let record = ctx.command_status(second).unwrap().unwrap();
+            assert_eq!(record.state, CommandState::Queued);
+            assert_eq!(record.started_at, None);
+
+            events.stopped(second).await;
+            let records = ctx.commands().unwrap();
+            assert_eq!(records.len(), 2);
+            assert!(records.iter().all(|r| r.state == CommandState::Finished));
+            assert_eq!(records[0].return_code, Some(0));
+            assert_eq!(records[1].return_code, Some(1));
+            assert!(records[1].started_at >= records[0].finished_at);
+            assert!(records[1].finished_at > records[1].started_at);
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn command_status_tail() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let mut ctx = Context::<StatusRuntime>::try_with(Env::temp(&["start"])).unwrap();
+            ctx.emitter = Some(Events::default().emitter());
+
+            // the tail limit falls within a 3-byte character
+            let output = "\u{20ac}".repeat(CommandRecord::OUTPUT_TAIL);
+            let command = command("ok", &[output.as_str()]);
+            let pid = StatusRuntime
+                .run_command(command, RuntimeMode::Server, &mut ctx)
+                .await
+                .unwrap();
+            tokio::task::yield_now().await;
+
+            let record = ctx.command_status(pid).unwrap().unwrap();
+            assert_eq!(record.stderr.len(), CommandRecord::OUTPUT_TAIL - 1);
+            let tail = String::from_utf8(record.stderr).unwrap();
+            assert!(tail.chars().all(|c| c == '\u{20ac}'));
+        })
+        .await;
+}
@@ -0,0 +1,110 @@
+mod utils;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use utils::{Env, Events};
+use ya_runtime_sdk::runtime_api::server::RuntimeService;
+use ya_runtime_sdk::server::Server;
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct SleepRuntime {
+    child: Arc<Mutex<Option<u32>>>,
+}
+
+impl_empty_runtime!(SleepRuntime, {
+    fn run_command<'a>(
+        &mut self,
+        command: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        let child_id = self.child.clone();
+        let mut cmd = tokio::process::Command::new(&command.bin);
+        cmd.args(&command.args);
+        ctx.command_for(&command, move |run_ctx| async move {
+            let mut child = run_ctx.spawn(cmd)?;
+            child_id.lock().unwrap().replace(child.id().unwrap());
+            if command.args.len() < 2 {
+                child.wait().await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn command_timeout(command: &RunProcess, ctx: &Context<Self>) -> Option<Duration> {
+        match command.args.first().map(String::as_str) {
+            Some("1") => Some(Duration::from_millis(100)),
+            _ => ctx.command_timeout,
+        }
+    }
+});
+
+fn sleep(args: &[&str]) -> RunProcess {
+    RunProcess {
+        bin: "sleep".to_string(),
+        args: args.iter().map(|s| s.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn command_timeout() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let mut ctx = Context::<SleepRuntime>::try_with(Env::temp(&["start"])).unwrap();
+            ctx.emitter = Some(events.emitter());
+            ctx.command_timeout = Some(Duration::from_millis(200));
+
+            let runtime = SleepRuntime::default();
+            let child = runtime.child.clone();
+            let server = Server::new(runtime, ctx);
+
+            // timeout hint derived from the request
+            let pid = server.run_process(sleep(&["1"])).await.unwrap().pid;
+            let status = events.stopped(pid).await;
+            assert_eq!(status.return_code, RETURN_CODE_TIMEOUT);
+            assert_eq!(
+                events.stderr(pid).concat(),
+                "Command timed out after 100ms\n"
+            );
+
+            #[cfg(target_os = "linux")]
+            {
+                let child = child.lock().unwrap().take().unwrap();
+                let stat = std::fs::read_to_string(format!("/proc/{}/stat", child));
+                assert!(stat.map(|s| s.contains(") Z ")).unwrap_or(true));
+            }
+
+            // default timeout
+            let pid = server.run_process(sleep(&["10"])).await.unwrap().pid;
+            let status = events.stopped(pid).await;
+            assert_eq!(status.return_code, RETURN_CODE_TIMEOUT);
+            assert_eq!(
+                events.stderr(pid).concat(),
+                "Command timed out after 200ms\n"
+            );
+
+            // completed in time
+            let pid = server.run_process(sleep(&["0"])).await.unwrap().pid;
+            let status = events.stopped(pid).await;
+            assert_eq!(status.return_code, 0);
+
+            // processes dropped by the handler are left running
+            let pid = server.run_process(sleep(&["1", "1"])).await.unwrap().pid;
+            let status = events.stopped(pid).await;
+            assert_eq!(status.return_code, 0);
+
+            #[cfg(target_os = "linux")]
+            {
+                let child = child.lock().unwrap().take().unwrap();
+                let stat = std::fs::read_to_string(format!("/proc/{}/stat", child)).unwrap();
+                assert!(!stat.contains(") Z "));
+                unsafe { libc::kill(child as libc::pid_t, libc::SIGKILL) };
+            }
+        })
+        .await;
+}
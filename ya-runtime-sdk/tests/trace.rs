@@ -0,0 +1,91 @@
+#![cfg(feature = "tracing")]
+
+mod utils;
+
+use std::sync::{Arc, Mutex};
+use tracing::span::{Attributes, Id};
+use tracing_subscriber::layer::{Context as LayerContext, SubscriberExt};
+use tracing_subscriber::Layer;
+use utils::{Env, Events};
+use ya_runtime_sdk::runtime_api::server::RuntimeService;
+use ya_runtime_sdk::server::Server;
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct TraceRuntime;
+
+impl_empty_runtime!(TraceRuntime, {
+    fn run_command<'a>(
+        &mut self,
+        command: RunProcess,
+        _: RuntimeMode,
+        ctx: &mut Context<Self>,
+    ) -> ProcessIdResponse<'a> {
+        ctx.command_for(&command, |_| async move { Ok(()) })
+    }
+});
+
+/// Span names and fields, formatted as `key=value`
+#[derive(Clone, Default)]
+struct Spans(Arc<Mutex<Vec<(String, Vec<String>)>>>);
+
+impl<S: tracing::Subscriber> Layer<S> for Spans {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: LayerContext<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        let name = attrs.metadata().name().to_string();
+        self.0.lock().unwrap().push((name, fields.0));
+    }
+}
+
+#[derive(Default)]
+struct Fields(Vec<String>);
+
+impl tracing::field::Visit for Fields {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.0.push(format!("{}={}", field.name(), value));
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0.push(format!("{}={:?}", field.name(), value));
+    }
+}
+
+impl Spans {
+    fn fields(&self, name: &str) -> Vec<Vec<String>> {
+        let spans = self.0.lock().unwrap();
+        spans
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, fields)| fields.clone())
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn command_spans() {
+    let spans = Spans::default();
+    let subscriber = tracing_subscriber::registry().with(spans.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let events = Events::default();
+            let mut ctx = Context::<TraceRuntime>::try_with(Env::temp(&["start"])).unwrap();
+            ctx.emitter = Some(events.emitter());
+            let server = Server::new(TraceRuntime, ctx);
+
+            let run = RunProcess {
+                bin: "echo".to_string(),
+                ..Default::default()
+            };
+            let pid = server.run_process(run).await.unwrap().pid;
+            events.stopped(pid).await;
+        })
+        .await;
+
+    assert_eq!(spans.fields("service"), vec![vec!["call=run_process"]]);
+    assert_eq!(spans.fields("run_process"), vec![vec!["command=echo"]]);
+    assert_eq!(spans.fields("command"), vec![vec!["pid=0", "command=echo"]]);
+}
@@ -1,36 +1,225 @@
+#![allow(dead_code)]
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use ya_runtime_sdk::cli::CommandCli;
+use ya_runtime_sdk::runtime_api::server::RuntimeHandler;
+use ya_runtime_sdk::*;
+
 #[macro_export]
 macro_rules! impl_empty_runtime {
     ($ty: ty) => {
-        impl ya_runtime_sdk::Runtime for $ty {
-            fn deploy<'a>(
+        impl_empty_runtime!($ty, {
+            fn stop<'a>(
                 &mut self,
                 _: &mut ya_runtime_sdk::Context<Self>,
-            ) -> ya_runtime_sdk::OutputResponse<'a> {
+            ) -> ya_runtime_sdk::EmptyResponse<'a> {
                 unimplemented!()
             }
 
-            fn start<'a>(
+            fn run_command<'a>(
                 &mut self,
+                _: ya_runtime_sdk::RunProcess,
+                _: ya_runtime_sdk::RuntimeMode,
                 _: &mut ya_runtime_sdk::Context<Self>,
-            ) -> ya_runtime_sdk::OutputResponse<'a> {
+            ) -> ya_runtime_sdk::ProcessIdResponse<'a> {
                 unimplemented!()
             }
-
-            fn stop<'a>(
+        });
+    };
+    ($ty: ty, { $($body: tt)* }) => {
+        impl ya_runtime_sdk::Runtime for $ty {
+            fn deploy<'a>(
                 &mut self,
                 _: &mut ya_runtime_sdk::Context<Self>,
-            ) -> ya_runtime_sdk::EmptyResponse<'a> {
+            ) -> ya_runtime_sdk::OutputResponse<'a> {
                 unimplemented!()
             }
 
-            fn run_command<'a>(
+            fn start<'a>(
                 &mut self,
-                _: ya_runtime_sdk::RunProcess,
-                _: ya_runtime_sdk::RuntimeMode,
                 _: &mut ya_runtime_sdk::Context<Self>,
-            ) -> ya_runtime_sdk::ProcessIdResponse<'a> {
+            ) -> ya_runtime_sdk::OutputResponse<'a> {
                 unimplemented!()
             }
+
+            $($body)*
         }
     };
 }
+
+/// Captured standard output
+#[derive(Clone, Default)]
+pub struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runtime environment with a fixed data directory, arguments and captured standard output
+#[derive(Clone)]
+pub struct Env {
+    dir: PathBuf,
+    args: Vec<String>,
+    stdout: Buffer,
+    temp_dir: Option<Arc<tempdir::TempDir>>,
+}
+
+impl Env {
+    /// Environment in a new temporary directory, removed with the last clone of `Env`
+    pub fn temp(args: &[&str]) -> Self {
+        let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+        let mut env = Self::new(temp_dir.path(), args);
+        env.temp_dir = Some(Arc::new(temp_dir));
+        env
+    }
+
+    /// Environment in an existing data directory
+    pub fn new(dir: impl AsRef<Path>, args: &[&str]) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+            stdout: Default::default(),
+            temp_dir: None,
+        }
+    }
+
+    /// Passes the data directory as `--workdir`
+    pub fn workdir(mut self) -> Self {
+        let workdir = vec!["--workdir".to_string(), self.dir.display().to_string()];
+        self.args.splice(0..0, workdir);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Standard output written so far
+    pub fn output(&self) -> String {
+        String::from_utf8(self.stdout.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl<C: CommandCli> ya_runtime_sdk::env::Env<C> for Env {
+    fn data_directory(&self, _: &str) -> anyhow::Result<PathBuf> {
+        Ok(self.dir.clone())
+    }
+
+    fn args(&self) -> Box<dyn Iterator<Item = String>> {
+        let name = std::iter::once(env!("CARGO_PKG_NAME").to_string());
+        Box::new(name.chain(self.args.clone()))
+    }
+
+    fn stdout(&self) -> Box<dyn Write> {
+        Box::new(self.stdout.clone())
+    }
+}
+
+/// Collects runtime events
+#[derive(Clone, Default)]
+pub struct Events {
+    pub process: Arc<Mutex<Vec<ProcessStatus>>>,
+    pub runtime: Arc<Mutex<Vec<RuntimeStatus>>>,
+}
+
+impl RuntimeHandler for Events {
+    fn on_process_status<'a>(&self, status: ProcessStatus) -> BoxFuture<'a, ()> {
+        self.process.lock().unwrap().push(status);
+        futures::future::ready(()).boxed()
+    }
+
+    fn on_runtime_status<'a>(&self, status: RuntimeStatus) -> BoxFuture<'a, ()> {
+        self.runtime.lock().unwrap().push(status);
+        futures::future::ready(()).boxed()
+    }
+}
+
+impl Events {
+    /// Spawns an emitter forwarding events to this collector
+    pub fn emitter(&self) -> EventEmitter {
+        EventEmitter::spawn(self.clone())
+    }
+
+    /// Process lifecycle events, as `(pid, running)` pairs
+    pub fn lifecycle(&self) -> Vec<(ProcessId, bool)> {
+        let process = self.process.lock().unwrap();
+        process
+            .iter()
+            .filter(|s| s.stdout.is_empty() && s.stderr.is_empty())
+            .map(|s| (s.pid, s.running))
+            .collect()
+    }
+
+    /// Waits for the stop event of a command
+    pub async fn stopped(&self, pid: ProcessId) -> ProcessStatus {
+        let find = || {
+            let process = self.process.lock().unwrap();
+            process.iter().find(|s| s.pid == pid && !s.running).cloned()
+        };
+        wait(find).await
+    }
+
+    /// Waits for stop events of `count` commands
+    pub async fn stopped_count(&self, count: usize) {
+        let stopped = || self.lifecycle().iter().filter(|(_, r)| !r).count();
+        wait(|| (stopped() >= count).then_some(())).await
+    }
+
+    /// Waits for a stdout chunk of a command
+    pub async fn stdout_chunk(&self, pid: ProcessId, chunk: &str) {
+        wait(|| self.stdout(pid).iter().any(|c| c == chunk).then_some(())).await
+    }
+
+    /// Non-empty stdout chunks of a command
+    pub fn stdout(&self, pid: ProcessId) -> Vec<String> {
+        self.output(pid, |s| &s.stdout)
+    }
+
+    /// Non-empty stderr chunks of a command
+    pub fn stderr(&self, pid: ProcessId) -> Vec<String> {
+        self.output(pid, |s| &s.stderr)
+    }
+
+    fn output(&self, pid: ProcessId, f: impl Fn(&ProcessStatus) -> &Vec<u8>) -> Vec<String> {
+        let process = self.process.lock().unwrap();
+        process
+            .iter()
+            .filter(|s| s.pid == pid && !f(s).is_empty())
+            .map(|s| String::from_utf8(f(s).clone()).unwrap())
+            .collect()
+    }
+}
+
+/// Polls `f` until it returns a value, failing the test after 5 seconds
+async fn wait<T>(f: impl Fn() -> Option<T>) -> T {
+    let poll = async {
+        loop {
+            match f() {
+                Some(value) => return value,
+                None => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), poll)
+        .await
+        .expect("Timed out waiting for events")
+}
+
+/// Lets spawned local tasks run
+pub async fn settle() {
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+}
@@ -0,0 +1,132 @@
+mod utils;
+
+use utils::Env;
+use ya_runtime_sdk::*;
+
+#[derive(ya_runtime_sdk::RuntimeDef, Default)]
+struct VolumeRuntime;
+
+impl_empty_runtime!(VolumeRuntime);
+
+#[test]
+fn volumes_add() {
+    let mut volumes = Volumes::default();
+    assert!(volumes.is_empty());
+
+    let volume = volumes.add("/in").unwrap();
+    assert_eq!(volume.name, "vol-in");
+    assert_eq!(volume.path, "/in");
+    let volume = volumes.add("/data/./out/").unwrap();
+    assert_eq!(volume.name, "vol-data-out");
+    assert_eq!(volume.path, "/data/out");
+    volumes.add_named("cache", "/var/cache").unwrap();
+    volumes.add("/in").unwrap();
+    assert_eq!(volumes.iter().count(), 3);
+
+    for (name, path) in [
+        ("vol-in", "/other"),
+        ("other", "/in"),
+        ("../escape", "/escape"),
+        ("relative", "relative"),
+        ("root", "/"),
+        ("parent", "/.."),
+    ] {
+        let err = volumes.add_named(name, path).unwrap_err();
+        assert_eq!(err.kind(), Some(ErrorKind::InvalidArgument));
+    }
+    assert_eq!(volumes.iter().count(), 3);
+}
+
+#[test]
+fn volumes_resolve() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let workdir = temp_dir.path();
+
+    let mut volumes = Volumes::default();
+    volumes.add("/data").unwrap();
+    volumes.add("/data/out").unwrap();
+    volumes.create(workdir).unwrap();
+    assert!(workdir.join("vol-data").is_dir());
+    assert!(workdir.join("vol-data-out").is_dir());
+
+    assert_eq!(
+        volumes.resolve(workdir, "/data/file").unwrap(),
+        workdir.join("vol-data").join("file")
+    );
+    assert_eq!(
+        volumes.resolve(workdir, "/data/out/dir/file").unwrap(),
+        workdir.join("vol-data-out").join("dir").join("file")
+    );
+    assert_eq!(
+        volumes.resolve(workdir, "/data/out/../file").unwrap(),
+        workdir.join("vol-data").join("file")
+    );
+
+    let err = volumes.resolve(workdir, "/etc/passwd").unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::NotFound));
+    let err = volumes.resolve(workdir, "/data/../etc/passwd").unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::NotFound));
+    let err = volumes.resolve(workdir, "/../../etc/passwd").unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::InvalidArgument));
+    let err = volumes.resolve(workdir, "data/file").unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::InvalidArgument));
+}
+
+#[cfg(unix)]
+#[test]
+fn volumes_resolve_symlink() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let workdir = temp_dir.path();
+
+    let mut volumes = Volumes::default();
+    volumes.add("/in").unwrap();
+    volumes.create(workdir).unwrap();
+
+    std::os::unix::fs::symlink(workdir, workdir.join("vol-in").join("escape")).unwrap();
+    let err = volumes.resolve(workdir, "/in/escape").unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::InvalidArgument));
+
+    // intermediate directory with a missing leaf
+    let err = volumes.resolve(workdir, "/in/escape/new/file").unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::InvalidArgument));
+
+    // dangling symbolic link
+    let missing = workdir.join("missing");
+    std::os::unix::fs::symlink(&missing, workdir.join("vol-in").join("dangling")).unwrap();
+    let err = volumes.resolve(workdir, "/in/dangling").unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::InvalidArgument));
+
+    // links within the volume and missing paths are accepted
+    std::fs::create_dir(workdir.join("vol-in").join("dir")).unwrap();
+    std::os::unix::fs::symlink("dir", workdir.join("vol-in").join("link")).unwrap();
+    assert_eq!(
+        volumes.resolve(workdir, "/in/link/new/file").unwrap(),
+        workdir.join("vol-in").join("link").join("new").join("file")
+    );
+}
+
+#[test]
+fn context_volumes_invalid() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let state_dir = temp_dir.path().join(StateStore::DIRECTORY);
+    std::fs::create_dir_all(&state_dir).unwrap();
+    std::fs::write(state_dir.join("ya-runtime-sdk.volumes.json"), "{invalid").unwrap();
+
+    let env = Env::new(temp_dir.path(), &["deploy"]).workdir();
+    let ctx = Context::<VolumeRuntime>::try_with(env).unwrap();
+    assert!(ctx.volumes.is_empty());
+}
+
+#[test]
+fn context_resolve_path() {
+    let temp_dir = tempdir::TempDir::new("ya-runtime-sdk").unwrap();
+    let env = Env::new(temp_dir.path(), &["deploy"]).workdir();
+    let mut ctx = Context::<VolumeRuntime>::try_with(env).unwrap();
+    assert!(ctx.volumes.is_empty());
+
+    ctx.volumes.add("/in").unwrap();
+    assert_eq!(
+        ctx.resolve_path("/in/file").unwrap(),
+        temp_dir.path().join("vol-in").join("file")
+    );
+}